@@ -0,0 +1,110 @@
+//! Plays the quiz in a terminal; commands start with `~` as on Discord and any other line
+//! is an answer.
+use mitama_test_bot::bot::{self, Session};
+use mitama_test_bot::commands::{parser, render};
+use mitama_test_bot::error::BotError;
+use mitama_test_bot::language::LANGUAGES;
+use std::env;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+~<language> [--unique] [--difficulty easy|normal|hard] [--len 5..8]
+~contest <number> [languages] [--unique] [--difficulty ...] [--len ...]
+~hint <number> [-r]
+~giveup
+~unrated
+~help
+~quit";
+
+fn main() {
+    lazy_static::initialize(&LANGUAGES);
+    let player = env::var("USER").unwrap_or_else(|_| "you".to_string());
+    let mut session = Session::default();
+    let mut rng = rand::thread_rng();
+    println!("languages: {}", LANGUAGES.codes().join(", "));
+    println!("{}", HELP);
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                println!("{}", err);
+                break;
+            }
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !line.starts_with('~') {
+            show(session.answer(0, &player, line, &mut rng));
+            continue;
+        }
+        let mut words = line[1..].split_whitespace().map(str::to_string);
+        let command = words.next().unwrap_or_default();
+        let events = match command.as_str() {
+            "quit" | "exit" => break,
+            "help" => {
+                println!("{}", HELP);
+                continue;
+            }
+            "giveup" => session.giveup(&mut rng),
+            "unrated" => Ok(session.abort()),
+            "hint" => match parser::hint(words) {
+                Ok(kind) => session.hint(kind, &mut rng),
+                Err(err) => {
+                    println!("{}", err);
+                    continue;
+                }
+            },
+            _ if !session.quiz.is_standing_by() => {
+                println!("前回の出題が解かれていません");
+                continue;
+            }
+            "contest" => match parser::contest(words) {
+                Ok((num, mut languages, filter)) => {
+                    if languages.is_empty() {
+                        languages = LANGUAGES.codes();
+                    }
+                    languages.sort();
+                    languages.dedup();
+                    session.start_contest(num, &languages, filter, &mut rng)
+                }
+                Err(err) => {
+                    println!("{}", err);
+                    continue;
+                }
+            },
+            command => match LANGUAGES.by_command(command) {
+                Some(lang) => match parser::quiz(command, words) {
+                    Ok(filter) => session.pose(lang, &filter, &mut rng),
+                    Err(err) => {
+                        println!("{}", err);
+                        continue;
+                    }
+                },
+                None => {
+                    println!("unknown command `~{}`; try `~help`.", command);
+                    continue;
+                }
+            },
+        };
+        show(events);
+    }
+}
+
+fn show(events: Result<Vec<bot::Event>, BotError>) {
+    match events {
+        Ok(events) => {
+            for event in &events {
+                println!("{}", render::render(event));
+            }
+        }
+        Err(err) => println!("{}", err),
+    }
+}
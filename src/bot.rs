@@ -1,83 +1,32 @@
 use super::dictionary::*;
+use super::error::{BotError, Lock};
+use super::history::{AlternativeRecord, ProblemRecord};
 use super::sort::Sorted;
-use crate::try_say;
 use indexmap::{IndexMap, IndexSet};
 use itertools::Itertools;
 use rand::distributions::{Distribution, Uniform};
-use serenity::client::Context;
-use serenity::model::channel::Message;
+use unicode_segmentation::UnicodeSegmentation;
 
 use std::ops::AddAssign;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-custom_derive! {
-    #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, NextVariant, PrevVariant)]
-    pub enum Lang {
-        En,
-        Ja,
-        Fr,
-        De,
-        It,
-        Ru,
-        Eo,
-    }
-}
-
-impl Lang {
-    pub fn as_symbol(self) -> String {
-        match self {
-            Lang::En => "英単語".to_string(),
-            Lang::Ja => "単語".to_string(),
-            Lang::Fr => "仏単語".to_string(),
-            Lang::De => "独単語".to_string(),
-            Lang::It => "伊単語".to_string(),
-            Lang::Ru => "露単語".to_string(),
-            Lang::Eo => "エスペラント単語".to_string(),
-        }
-    }
-}
+pub use super::language::Lang;
+use super::language::{Accents, Normalization};
 
-impl<S: Into<String>> From<S> for Lang {
-    fn from(s: S) -> Self {
-        let lang: String = s.into();
-        match lang {
-            en if en == "en" => Lang::En,
-            ja if ja == "ja" => Lang::Ja,
-            fr if fr == "fr" => Lang::Fr,
-            de if de == "de" => Lang::De,
-            it if it == "it" => Lang::It,
-            ru if ru == "ru" => Lang::Ru,
-            eo if eo == "eo" => Lang::Eo,
-            _ => panic!("unexpected language token!"),
-        }
-    }
+pub fn get_dictionary(lang: Lang) -> Result<Arc<Dictionary>, BotError> {
+    lang.dictionary()
 }
 
-pub fn get_dictionary(lang: Lang) -> &'static Dictionary {
-    match lang {
-        Lang::En => &*ENGLISH,
-        Lang::Ja => &*JAPANESE,
-        Lang::Fr => &*FRENCH,
-        Lang::De => &*GERMAN,
-        Lang::It => &*ITALIAN,
-        Lang::Ru => &*RUSSIAN,
-        Lang::Eo => &*ESPERANTO,
-    }
+pub fn select_dictionary_from_str<S: AsRef<str>>(lang: S) -> Result<Arc<Dictionary>, BotError> {
+    get_dictionary(lang.as_ref().parse()?)
 }
 
-pub fn select_dictionary_from_str<S: Into<String>>(lang: S) -> &'static Dictionary {
-    let lang_string: String = lang.into();
-    match lang_string {
-        en if en == "en" => &*ENGLISH,
-        ja if ja == "ja" => &*JAPANESE,
-        fr if fr == "fr" => &*FRENCH,
-        de if de == "de" => &*GERMAN,
-        it if it == "it" => &*ITALIAN,
-        ru if ru == "ru" => &*RUSSIAN,
-        eo if eo == "eo" => &*ESPERANTO,
-        _ => panic!("unexpected language token!"),
-    }
+/// Whether `got` is one of `words` once the diacritics are stripped.
+fn near_miss<'a, I: IntoIterator<Item = &'a String>>(words: I, got: &str) -> bool {
+    let fold = |word: &str| Normalization::Diacritics.apply(word);
+    let got = fold(got);
+    words.into_iter().any(|word| fold(word) == got)
 }
 
 #[derive(Debug)]
@@ -87,10 +36,20 @@ pub enum Status {
     Contesting(String, Lang, (u32, u32), Instant),
 }
 
+impl Default for Status {
+    fn default() -> Self {
+        Status::StandingBy
+    }
+}
+
 pub enum CheckResult<'a> {
     Assumed(&'a str),
-    Anagram(&'a str),
-    Full(&'a str),
+    /// Another solution in the questions, with its dictionary spelling.
+    Anagram(String),
+    /// Another solution only in the full dictionary, with its dictionary spelling.
+    Full(String),
+    /// Only the diacritics differ from a solution; see [`Accents::NearMiss`].
+    NearMiss(&'a str),
     WA,
 }
 
@@ -121,52 +80,111 @@ impl Status {
         }
     }
 
-    pub fn get_dictionary(&self) -> Result<&Dictionary, ()> {
+    pub fn get_dictionary(&self) -> Result<Option<Arc<Dictionary>>, BotError> {
         match self {
-            Status::StandingBy => Err(()),
+            Status::StandingBy => Ok(None),
             Status::Contesting(_, lang, ..) | Status::Holding(_, lang, ..) => {
-                Ok(get_dictionary(*lang))
+                get_dictionary(*lang).map(Some)
             }
         }
     }
 
-    pub fn is_correct_answer(&self, got: &str) -> bool {
+    /// Normalizes an answer with the rules of the current language.
+    pub fn normalize(&self, got: &str) -> String {
         match self {
-            Status::StandingBy => false,
-            Status::Contesting(ans, ..) | Status::Holding(ans, ..) => ans == &got.to_lowercase(),
+            Status::StandingBy => got.to_lowercase(),
+            Status::Contesting(_, lang, ..) | Status::Holding(_, lang, ..) => lang.normalize(got),
         }
     }
 
-    pub fn is_anagram(&self, got: &str) -> bool {
+    /// The dictionary spelling of an answer, or its normalized form if it is not in the
+    /// dictionary of the current language.
+    pub fn spelling(&self, got: &str) -> Result<String, BotError> {
+        Ok(match self {
+            Status::StandingBy => got.to_lowercase(),
+            Status::Contesting(_, lang, ..) | Status::Holding(_, lang, ..) => lang
+                .dictionary()?
+                .spelling(got)
+                .cloned()
+                .unwrap_or_else(|| lang.normalize(got)),
+        })
+    }
+
+    pub fn is_correct_answer(&self, got: &str) -> bool {
         match self {
             Status::StandingBy => false,
-            Status::Contesting(ans, ..) | Status::Holding(ans, ..) => {
-                ans.sorted() == got.to_lowercase().sorted()
-                    && self.get_dictionary().unwrap().contains(&got.to_lowercase())
+            Status::Contesting(ans, lang, ..) | Status::Holding(ans, lang, ..) => {
+                lang.read(ans) == lang.read(got)
             }
         }
     }
 
-    pub fn is_anagram_by_full(&self, got: &str) -> bool {
-        match self {
-            Status::StandingBy => false,
-            _ => {
-                self.ans().unwrap().sorted() == got.to_lowercase().sorted()
-                    && self
-                        .get_dictionary()
-                        .unwrap()
-                        .contains_ex(&got.to_lowercase())
+    pub fn is_anagram(&self, got: &str) -> Result<bool, BotError> {
+        Ok(match self.answer_check(got)? {
+            CheckResult::Anagram(_) => true,
+            _ => false,
+        })
+    }
+
+    pub fn is_anagram_by_full(&self, got: &str) -> Result<bool, BotError> {
+        Ok(match self.answer_check(got)? {
+            CheckResult::Full(_) => true,
+            _ => false,
+        })
+    }
+
+    /// Judges an answer with a single lookup in the anagram index of the dictionary.
+    pub fn answer_check<'a>(&self, msg: &'a str) -> Result<CheckResult<'a>, BotError> {
+        Ok(match self {
+            Status::StandingBy => CheckResult::WA,
+            Status::Contesting(ans, lang, ..) | Status::Holding(ans, lang, ..) => {
+                let reading = lang.read(msg);
+                if lang.read(ans) == reading {
+                    return Ok(CheckResult::Assumed(msg));
+                }
+                let dic = lang.dictionary()?;
+                let words = match dic.anagrams(ans) {
+                    Some(words) => words,
+                    None => return Ok(CheckResult::WA),
+                };
+                let got = lang.normalize(msg);
+                let solution = words
+                    .get_full(&got)
+                    .map(|(_, word, spelling)| (word, spelling))
+                    .or_else(|| words.iter().find(|(word, _)| lang.read(word) == reading));
+                if let Some((word, spelling)) = solution {
+                    if dic.contains(word) {
+                        CheckResult::Anagram(spelling.clone())
+                    } else {
+                        CheckResult::Full(spelling.clone())
+                    }
+                } else if lang.accents() == Accents::NearMiss && near_miss(words.keys(), &got) {
+                    CheckResult::NearMiss(msg)
+                } else {
+                    CheckResult::WA
+                }
             }
-        }
+        })
     }
 
-    pub fn answer_check<'a>(&self, msg: &'a str) -> CheckResult<'a> {
-        match self {
-            _ if self.is_correct_answer(msg) => CheckResult::Assumed(msg),
-            _ if self.is_anagram(msg) => CheckResult::Anagram(msg),
-            _ if self.is_anagram_by_full(msg) => CheckResult::Full(msg),
-            _ => CheckResult::WA,
-        }
+    /// Valid solutions other than the expected answer.
+    pub fn other_solutions(&self) -> Result<Vec<String>, BotError> {
+        Ok(match self {
+            Status::StandingBy => Vec::new(),
+            Status::Contesting(ans, lang, ..) | Status::Holding(ans, lang, ..) => {
+                let ans = lang.normalize(ans);
+                lang.dictionary()?
+                    .anagrams(&ans)
+                    .map(|words| {
+                        words
+                            .iter()
+                            .filter(|(word, _)| **word != ans)
+                            .map(|(_, spelling)| spelling.clone())
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            }
+        })
     }
 
     pub fn is_contest_end(&self) -> bool {
@@ -183,27 +201,37 @@ impl Status {
         }
     }
 
-    pub fn contest_continue(&mut self, ctx: &mut Context, msg: &Message) {
-        let (dic, lang) = CONTEST_LIBRARY
-            .lock()
-            .unwrap()
-            .select(&mut rand::thread_rng());
-        let ans = dic.get(&mut rand::thread_rng());
+    pub fn contest_continue<Engine: rand::Rng>(
+        &mut self,
+        library: &DictionarySelector,
+        rng: &mut Engine,
+    ) -> Result<Event, BotError> {
+        let (dic, lang) = library.select(rng)?;
+        // the filter was validated when the contest started; fall back if a reload broke it.
+        let ans = match dic.pick(&library.filter, rng) {
+            Some(ans) => ans,
+            None => dic.get(rng),
+        };
         let sorted = ans.sorted();
         println!("called contest_continue: [{}, {}]", ans, sorted);
         let (count, num) = self.get_contest_num().unwrap();
-        try_say!(
-            ctx,
-            msg,
-            format!(
-                "問 {current} ({current}/{number})\nソートなぞなぞ ソート前の {symbol} な〜んだ？\n`{prob}`",
-                number = num,
-                current = *count + 1,
-                prob = sorted,
-                symbol = lang.as_symbol(),
-            )
-        );
-        *self = Status::Contesting(ans.to_string(), lang, (*count + 1, *num), Instant::now());
+        let progress = (*count + 1, *num);
+        let started = Instant::now();
+        *self = Status::Contesting(ans.to_string(), lang, progress, started);
+        Ok(Event::QuestionPosed {
+            lang,
+            sorted,
+            progress: Some(progress),
+            started,
+        })
+    }
+
+    /// When the current question was posed; identifies the question.
+    pub fn started(&self) -> Option<Instant> {
+        match self {
+            Status::Holding(_, _, instant) | Status::Contesting(_, _, _, instant) => Some(*instant),
+            _ => None,
+        }
     }
 
     pub fn elapsed(&self) -> Option<f32> {
@@ -218,6 +246,7 @@ impl Status {
 pub struct DictionarySelector {
     engine: Result<Lang, Uniform<usize>>,
     set: IndexSet<Lang>,
+    pub filter: Filter,
 }
 
 impl Default for DictionarySelector {
@@ -229,32 +258,57 @@ impl Default for DictionarySelector {
 impl DictionarySelector {
     pub fn new() -> DictionarySelector {
         DictionarySelector {
-            engine: Ok(Lang::En),
+            engine: Ok(Lang::default()),
             set: Default::default(),
+            filter: Default::default(),
         }
     }
-    pub fn init<S: Into<String>>(&mut self, languages: Vec<S>) {
+    pub fn init<S: AsRef<str>>(&mut self, languages: &[S], filter: Filter) -> Result<(), BotError> {
+        let languages = languages
+            .iter()
+            .map(|lang| lang.as_ref().parse::<Lang>())
+            .collect::<Result<IndexSet<_>, _>>()?;
+        self.filter = filter;
         self.set = IndexSet::new();
-        if languages.len() == 1 {
-            self.engine = Ok(Lang::from(languages.into_iter().next().unwrap()));
-        } else {
+        if languages.len() > 1 {
             self.engine = Err(Uniform::new(0, languages.len()));
-            for lang in languages {
-                self.set.insert(Lang::from(lang));
-            }
+            self.set = languages;
+        } else {
+            self.engine = Ok(*languages.get_index(0).ok_or(BotError::NoLanguage)?);
         }
+        Ok(())
     }
-    pub fn select<Engine: rand::Rng>(&self, rng: &mut Engine) -> (&'static Dictionary, Lang) {
+    pub fn select<Engine: rand::Rng>(
+        &self,
+        rng: &mut Engine,
+    ) -> Result<(Arc<Dictionary>, Lang), BotError> {
         let lang = *self
             .engine
             .as_ref()
             .unwrap_or_else(|uniform| self.set.get_index(uniform.sample(rng)).unwrap());
-        (get_dictionary(lang), lang)
+        Ok((get_dictionary(lang)?, lang))
+    }
+    pub fn languages(&self) -> Vec<Lang> {
+        match &self.engine {
+            Ok(lang) => vec![*lang],
+            Err(_) => self.set.iter().cloned().collect(),
+        }
     }
 }
 
-#[derive(Default, Debug)]
+/// Whom a point of a contest goes to; questions given up are scored to `GaveUp`, which is
+/// never rated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Contestant {
+    /// A user, by id.
+    Player(u64),
+    GaveUp,
+}
+
+/// Score of a contestant; `name` is the display name it was last seen with.
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct ContestData {
+    pub name: String,
     pub time: Vec<f32>,
 }
 
@@ -281,27 +335,419 @@ impl AddAssign<f32> for ContestData {
     }
 }
 
-pub fn aggregates(contest_result: &IndexMap<String, ContestData>) -> String {
-    use ordinal::Ordinal;
+/// Sorts contest results into the final ranking.
+pub fn standings(
+    contest_result: &IndexMap<Contestant, ContestData>,
+) -> Vec<(Contestant, ContestData)> {
     contest_result
         .iter()
         .sorted_by_key(|(_, data)| data.key())
+        .map(|(contestant, data)| (*contestant, data.clone()))
+        .collect()
+}
+
+pub fn aggregates(standings: &[(Contestant, ContestData)]) -> String {
+    use ordinal::Ordinal;
+    standings
+        .iter()
         .enumerate()
-        .map(|(index, (name, data))| {
+        .map(|(index, (_, data))| {
             format!(
                 "{}: {}, {}\n",
                 Ordinal(index + 1).to_string(),
-                name,
+                data.name,
                 data.as_string()
             )
         })
         .collect::<String>()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hint {
+    First(usize),
+    Random(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HintError {
+    NoQuestion,
+    Zero,
+    Unique,
+    TooLong,
+}
+
+/// Builds the hint text for `ans`.
+///
+/// Hints revealing the whole answer (or all but one grapheme) are rejected with `HintError::Unique`.
+pub fn hint<Engine: rand::Rng>(
+    ans: &str,
+    hint: Hint,
+    rng: &mut Engine,
+) -> Result<String, HintError> {
+    let g = UnicodeSegmentation::graphemes(ans, true).collect::<Vec<&str>>();
+    match hint {
+        Hint::First(num) | Hint::Random(num) if num == 0 => Err(HintError::Zero),
+        Hint::First(num) | Hint::Random(num) if num == g.len() || num == g.len() - 1 => {
+            Err(HintError::Unique)
+        }
+        Hint::First(num) | Hint::Random(num) if num > g.len() => Err(HintError::TooLong),
+        Hint::First(num) => Ok(g[..num].concat()),
+        Hint::Random(num) => {
+            let mut hint_str: Vec<&str> = std::iter::repeat("*").take(g.len()).collect();
+            for idx in rand::seq::index::sample(rng, g.len(), num).into_iter() {
+                hint_str[idx] = g[idx];
+            }
+            Ok(hint_str.join(""))
+        }
+    }
+}
+
+/// Outcome of a game operation, to be rendered by a frontend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// `progress` is `(current, total)` while contesting.
+    QuestionPosed {
+        lang: Lang,
+        sorted: String,
+        progress: Option<(u32, u32)>,
+        started: Instant,
+    },
+    ContestStarted {
+        number: u32,
+    },
+    /// `others` lists the other valid solutions of the puzzle.
+    Solved {
+        player: String,
+        answer: String,
+        elapsed: f32,
+        others: Vec<String>,
+    },
+    /// An anagram other than the expected answer; `in_questions` tells which word list it came from.
+    AlternativeSolved {
+        player: String,
+        answer: String,
+        in_questions: bool,
+    },
+    Revealed {
+        answer: String,
+        others: Vec<String>,
+    },
+    /// `answer` would be correct with the right diacritics; the question stays open.
+    NearMiss {
+        player: String,
+        answer: String,
+    },
+    ContestFinished {
+        number: u32,
+        languages: Vec<Lang>,
+        standings: Vec<(Contestant, ContestData)>,
+        problems: Vec<ProblemRecord>,
+    },
+    ContestAborted,
+    /// The time limit of the question has been reached.
+    TimedOut,
+    Remaining {
+        seconds: u64,
+    },
+    /// No question of `lang` satisfies the requested filter.
+    NoCandidate {
+        lang: Lang,
+    },
+    NotPosed,
+    NotContesting,
+    HintGiven {
+        hint: Hint,
+        text: String,
+    },
+    HintRejected(HintError),
+}
+
+/// Quiz state owned by a single channel (or DM).
+#[derive(Default)]
+pub struct Session {
+    pub quiz: Status,
+    pub contest_result: IndexMap<Contestant, ContestData>,
+    pub contest_library: DictionarySelector,
+    /// Questions of the current contest so far.
+    pub problems: Vec<ProblemRecord>,
+}
+
+impl Session {
+    pub fn pose<Engine: rand::Rng>(
+        &mut self,
+        lang: Lang,
+        filter: &Filter,
+        rng: &mut Engine,
+    ) -> Result<Vec<Event>, BotError> {
+        let dic = get_dictionary(lang)?;
+        let ans = match dic.pick(filter, rng) {
+            Some(ans) => ans,
+            None => return Ok(vec![Event::NoCandidate { lang }]),
+        };
+        let sorted = ans.sorted();
+        println!("called prob: [{}, {}]", ans, sorted);
+        let started = Instant::now();
+        self.quiz = Status::Holding(ans.clone(), lang, started);
+        Ok(vec![Event::QuestionPosed {
+            lang,
+            sorted,
+            progress: None,
+            started,
+        }])
+    }
+
+    pub fn start_contest<S: AsRef<str>, Engine: rand::Rng>(
+        &mut self,
+        number: u32,
+        languages: &[S],
+        filter: Filter,
+        rng: &mut Engine,
+    ) -> Result<Vec<Event>, BotError> {
+        self.contest_library.init(languages, filter)?;
+        for lang in self.contest_library.languages() {
+            if get_dictionary(lang)?.pick(&filter, rng).is_none() {
+                return Ok(vec![Event::NoCandidate { lang }]);
+            }
+        }
+        self.contest_result = IndexMap::new();
+        let (dic, lang) = self.contest_library.select(rng)?;
+        let ans = dic.pick(&filter, rng).unwrap();
+        let sorted = ans.sorted();
+        println!("called contest: [{}, {}]", ans, sorted);
+        self.problems = vec![ProblemRecord::new(lang, sorted.clone(), ans.clone())];
+        let started = Instant::now();
+        self.quiz = Status::Contesting(ans.clone(), lang, (1, number), started);
+        Ok(vec![
+            Event::ContestStarted { number },
+            Event::QuestionPosed {
+                lang,
+                sorted,
+                progress: Some((1, number)),
+                started,
+            },
+        ])
+    }
+
+    /// Checks `content` sent by the user `id`, shown as `player`.
+    pub fn answer<Engine: rand::Rng>(
+        &mut self,
+        id: u64,
+        player: &str,
+        content: &str,
+        rng: &mut Engine,
+    ) -> Result<Vec<Event>, BotError> {
+        let elapsed = match self.quiz.elapsed() {
+            Some(elapsed) => elapsed,
+            None => return Ok(vec![]),
+        };
+        Ok(match self.quiz.answer_check(content)? {
+            CheckResult::WA => vec![],
+            CheckResult::NearMiss(got) => vec![Event::NearMiss {
+                player: player.to_string(),
+                answer: got.to_string(),
+            }],
+            CheckResult::Assumed(_) => {
+                let mut events = vec![Event::Solved {
+                    player: player.to_string(),
+                    answer: self.quiz.ans().unwrap().clone(),
+                    elapsed,
+                    others: self.quiz.other_solutions()?,
+                }];
+                if self.quiz.is_holding() {
+                    self.quiz = Status::StandingBy;
+                } else {
+                    self.record(Contestant::Player(id), player, elapsed);
+                    if let Some(problem) = self.problems.last_mut() {
+                        problem.solver = Some(player.to_string());
+                        problem.elapsed = Some(elapsed);
+                    }
+                    events.push(self.advance(rng)?);
+                }
+                events
+            }
+            CheckResult::Anagram(answer) => self.alternative(id, player, answer, true, elapsed),
+            CheckResult::Full(answer) => self.alternative(id, player, answer, false, elapsed),
+        })
+    }
+
+    /// Announces an alternative solution; while contesting, only the first one a player finds
+    /// for a question is credited.
+    fn alternative(
+        &mut self,
+        id: u64,
+        player: &str,
+        answer: String,
+        in_questions: bool,
+        elapsed: f32,
+    ) -> Vec<Event> {
+        let credited = self.quiz.is_contesting()
+            && match self.problems.last_mut() {
+                Some(problem) if !problem.alternatives.iter().any(|alt| alt.id == id) => {
+                    problem.alternatives.push(AlternativeRecord {
+                        id,
+                        player: player.to_string(),
+                        answer: answer.clone(),
+                        elapsed,
+                    });
+                    true
+                }
+                _ => false,
+            };
+        if credited {
+            self.record(Contestant::Player(id), player, elapsed);
+        }
+        vec![Event::AlternativeSolved {
+            player: player.to_string(),
+            answer,
+            in_questions,
+        }]
+    }
+
+    pub fn giveup<Engine: rand::Rng>(&mut self, rng: &mut Engine) -> Result<Vec<Event>, BotError> {
+        let answer = match self.quiz.ans() {
+            Ok(ans) => ans.clone(),
+            Err(_) => return Ok(vec![Event::NotPosed]),
+        };
+        let mut events = vec![Event::Revealed {
+            answer,
+            others: self.quiz.other_solutions()?,
+        }];
+        if self.quiz.is_holding() {
+            self.quiz = Status::StandingBy;
+        } else {
+            let elapsed = self.quiz.elapsed().unwrap();
+            self.record(Contestant::GaveUp, "~giveup", elapsed);
+            if let Some(problem) = self.problems.last_mut() {
+                problem.gave_up = true;
+            }
+            events.push(self.advance(rng)?);
+        }
+        Ok(events)
+    }
+
+    pub fn hint<Engine: rand::Rng>(
+        &mut self,
+        kind: Hint,
+        rng: &mut Engine,
+    ) -> Result<Vec<Event>, BotError> {
+        let ans = match self.quiz.ans() {
+            Ok(ans) => ans.clone(),
+            Err(_) => return Ok(vec![Event::HintRejected(HintError::NoQuestion)]),
+        };
+        Ok(match hint(&ans, kind, rng) {
+            Ok(text) => vec![Event::HintGiven { hint: kind, text }],
+            Err(HintError::Unique) => {
+                let mut events = vec![Event::HintRejected(HintError::Unique)];
+                events.extend(self.giveup(rng)?);
+                events
+            }
+            Err(err) => vec![Event::HintRejected(err)],
+        })
+    }
+
+    /// Reminds the remaining time if the question posed at `started` is still open.
+    pub fn warn(&self, started: Instant, seconds: u64) -> Vec<Event> {
+        if self.quiz.started() == Some(started) {
+            vec![Event::Remaining { seconds }]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Gives up the question posed at `started` if it is still open.
+    pub fn expire<Engine: rand::Rng>(
+        &mut self,
+        started: Instant,
+        rng: &mut Engine,
+    ) -> Result<Vec<Event>, BotError> {
+        if self.quiz.started() == Some(started) {
+            let mut events = vec![Event::TimedOut];
+            events.extend(self.giveup(rng)?);
+            Ok(events)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// Closes the current contest without announcing its standings.
+    pub fn abort(&mut self) -> Vec<Event> {
+        if self.quiz.is_contesting() {
+            self.quiz = Status::StandingBy;
+            self.contest_result = IndexMap::new();
+            self.problems = Vec::new();
+            vec![Event::ContestAborted]
+        } else {
+            vec![Event::NotContesting]
+        }
+    }
+
+    fn record(&mut self, contestant: Contestant, name: &str, elapsed: f32) {
+        let data = self
+            .contest_result
+            .entry(contestant)
+            .or_insert_with(ContestData::default);
+        data.name = name.to_string();
+        *data += elapsed;
+    }
+
+    fn advance<Engine: rand::Rng>(&mut self, rng: &mut Engine) -> Result<Event, BotError> {
+        if self.quiz.is_contest_end() {
+            let (_, num) = self.quiz.get_contest_num().unwrap();
+            let event = Event::ContestFinished {
+                number: *num,
+                languages: self.contest_library.languages(),
+                standings: standings(&self.contest_result),
+                problems: std::mem::replace(&mut self.problems, Vec::new()),
+            };
+            self.contest_result = IndexMap::new();
+            self.quiz = Status::StandingBy;
+            Ok(event)
+        } else {
+            let event = self.quiz.contest_continue(&self.contest_library, rng)?;
+            if let (Event::QuestionPosed { lang, sorted, .. }, Ok(ans)) = (&event, self.quiz.ans())
+            {
+                self.problems
+                    .push(ProblemRecord::new(*lang, sorted.clone(), ans.clone()));
+            }
+            Ok(event)
+        }
+    }
+}
+
 lazy_static! {
-    pub static ref QUIZ: Arc<Mutex<Status>> = Arc::new(Mutex::new(Status::StandingBy));
-    pub static ref CONTEST_RESULT: Arc<Mutex<IndexMap<String, ContestData>>> =
+    pub static ref SESSIONS: Arc<Mutex<IndexMap<u64, Arc<Mutex<Session>>>>> =
         Arc::new(Mutex::new(IndexMap::new()));
-    pub static ref CONTEST_LIBRARY: Arc<Mutex<DictionarySelector>> =
-        Arc::new(Mutex::new(DictionarySelector::new()));
+}
+
+/// Returns the session of the channel, creating an empty one on first use.
+pub fn session(channel: u64) -> Result<Arc<Mutex<Session>>, BotError> {
+    Ok(SESSIONS
+        .locked()?
+        .entry(channel)
+        .or_insert_with(|| Arc::new(Mutex::new(Session::default())))
+        .clone())
+}
+
+/// Returns the session of the channel if it has one, without creating it.
+pub fn find_session(channel: u64) -> Result<Option<Arc<Mutex<Session>>>, BotError> {
+    Ok(SESSIONS.locked()?.get(&channel).cloned())
+}
+
+/// Drops the session of the channel once it is back to standing by.
+///
+/// Sessions still referenced elsewhere (e.g. by a pending timeout) are kept; the caller must
+/// release its own reference first.
+pub fn release(channel: u64) -> Result<(), BotError> {
+    let mut sessions = SESSIONS.locked()?;
+    let idle = match sessions.get(&channel) {
+        Some(session) if Arc::strong_count(session) == 1 => {
+            let guard = session.locked()?;
+            guard.quiz.is_standing_by() && guard.contest_result.is_empty()
+        }
+        _ => false,
+    };
+    if idle {
+        sessions.shift_remove(&channel);
+    }
+    Ok(())
 }
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serenity::{http::Http, model::id::ChannelId};
+use std::sync::Arc;
+
+/// A chat message as seen by the bot, independent of the transport.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub author: String,
+    pub author_id: u64,
+    pub bot: bool,
+    pub channel: u64,
+    /// `None` for direct messages.
+    pub guild: Option<u64>,
+    /// Role ids of the author in the guild.
+    pub roles: Vec<u64>,
+    /// Whether the author has the administrator permission of the guild.
+    pub administrator: bool,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Destination of the bot's messages.
+pub trait Chat: Send + Sync {
+    fn say(&self, channel: u64, text: String);
+}
+
+pub type Transport = Arc<dyn Chat>;
+
+/// Posts to Discord through the REST API.
+pub struct Discord(pub Arc<Http>);
+
+impl Chat for Discord {
+    fn say(&self, channel: u64, text: String) {
+        if let Err(why) = ChannelId(channel).say(&self.0, text) {
+            println!("{}", why);
+        }
+    }
+}
+
+/// Roles and permissions are left empty; they are looked up in the cache by the handler.
+impl From<&serenity::model::channel::Message> for Message {
+    fn from(msg: &serenity::model::channel::Message) -> Self {
+        Message {
+            author: msg.author.name.clone(),
+            author_id: *msg.author.id.as_u64(),
+            bot: msg.author.bot,
+            channel: *msg.channel_id.as_u64(),
+            guild: msg.guild_id.map(|guild| *guild.as_u64()),
+            roles: Vec::new(),
+            administrator: false,
+            content: msg.content.clone(),
+            timestamp: msg.timestamp.with_timezone(&Utc),
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub(crate) mod executors;
 pub(crate) mod facade;
-pub(crate) mod parser;
+pub mod parser;
+pub mod render;
+pub mod router;
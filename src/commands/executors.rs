@@ -1,44 +1,294 @@
-use serenity::{model::channel::Message, prelude::*};
-
-use super::super::bot;
-use super::super::bot::ContestData;
-use super::super::dictionary;
+use super::super::chat::{Message, Transport};
+use super::super::error::{BotError, Lock};
+use super::super::language;
 use super::super::sort::Sorted;
-use indexmap::IndexMap;
+use super::super::{bot, history, rating, sandbox, settings};
+use super::{parser, render};
+use itertools::Itertools;
 
 use crate::try_say;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::str::from_utf8;
-
-pub(crate) fn prob(ctx: &mut Context, msg: &Message, lang: bot::Lang) -> String {
-    let dic = match lang {
-        bot::Lang::En => &*dictionary::ENGLISH,
-        bot::Lang::Ja => &*dictionary::JAPANESE,
-        bot::Lang::Fr => &*dictionary::FRENCH,
-        bot::Lang::De => &*dictionary::GERMAN,
-        bot::Lang::It => &*dictionary::ITALIAN,
-        bot::Lang::Ru => &*dictionary::RUSSIAN,
-        bot::Lang::Eo => &*dictionary::ESPERANTO,
+use std::collections::HashMap;
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Writes `text` the way the channel prefers, i.e. Esperanto letters in the x-system.
+fn for_channel(channel: u64, guild: Option<u64>, text: String) -> Result<String, BotError> {
+    if settings::SETTINGS.locked()?.x_system(channel, guild) {
+        Ok(language::to_x_system(&text))
+    } else {
+        Ok(text)
+    }
+}
+
+/// Posts the rendered events to the channel as a single message, recording and rating
+/// finished contests and scheduling the time limit of posed questions.
+pub(crate) fn post(chat: &Transport, channel: u64, events: &[bot::Event]) -> Result<(), BotError> {
+    if !events.is_empty() {
+        let text = events.iter().map(render::render).join("\n");
+        chat.say(channel, for_channel(channel, None, text)?);
+    }
+    for event in events {
+        match event {
+            bot::Event::ContestFinished {
+                number,
+                languages,
+                standings,
+                problems,
+            } => {
+                let record = history::ContestRecord {
+                    date: chrono::Utc::now(),
+                    channel,
+                    number: *number,
+                    languages: languages
+                        .iter()
+                        .map(|lang| lang.as_code().to_string())
+                        .collect(),
+                    problems: problems.clone(),
+                    standings: standings.iter().map(history::Standing::from).collect(),
+                };
+                if let Err(err) = history::save(record) {
+                    println!("{:?}", err);
+                }
+                match rating::rate(standings) {
+                    Ok(changes) if !changes.is_empty() => {
+                        chat.say(
+                            channel,
+                            format!(
+                                "レート変動:\n{}",
+                                changes
+                                    .iter()
+                                    .map(rating::RatingChange::as_string)
+                                    .join("\n")
+                            ),
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(err) => println!("{:?}", err),
+                }
+            }
+            bot::Event::QuestionPosed { started, .. } => {
+                schedule_timeout(chat.clone(), channel, *started)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Question whose time limit the timer of its channel is counting down.
+struct Countdown {
+    started: Instant,
+    limit: settings::timeout::Limit,
+    warned: bool,
+}
+
+impl Countdown {
+    /// Remaining seconds to remind at, unless already reminded.
+    fn warning(&self) -> Option<u64> {
+        if self.warned {
+            return None;
+        }
+        self.limit
+            .warning
+            .filter(|warning| *warning < self.limit.seconds)
+    }
+
+    /// When the reminder or the time limit is due.
+    fn deadline(&self) -> Instant {
+        let remaining = self.warning().unwrap_or(0);
+        self.started + Duration::from_secs(self.limit.seconds - remaining)
+    }
+}
+
+lazy_static! {
+    /// Timer of each channel with a question counting down; a newly posed question replaces
+    /// the previous one, and the thread ends once none is left.
+    static ref TIMERS: Mutex<HashMap<u64, mpsc::Sender<Countdown>>> = Mutex::new(HashMap::new());
+}
+
+/// Reminds and gives up the question posed at `started` according to the channel's time limit.
+fn schedule_timeout(chat: Transport, channel: u64, started: Instant) -> Result<(), BotError> {
+    let limit = match settings::SETTINGS.locked()?.timeout(channel, None) {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+    let countdown = Countdown {
+        started,
+        limit,
+        warned: false,
+    };
+    let mut timers = TIMERS.locked()?;
+    let countdown = match timers.get(&channel) {
+        Some(timer) => match timer.send(countdown) {
+            Ok(()) => return Ok(()),
+            Err(mpsc::SendError(countdown)) => countdown,
+        },
+        None => countdown,
     };
-    let ans = dic.get(&mut rand::thread_rng());
-    let sorted = ans.sorted();
-    try_say!(
-        ctx,
-        msg,
-        format!(
-            "ソートなぞなぞ ソート前の {as_str} な〜んだ？\n`{prob}`",
-            as_str = lang.as_symbol(),
-            prob = sorted
-        )
+    let (timer, countdowns) = mpsc::channel();
+    timer.send(countdown).ok();
+    timers.insert(channel, timer);
+    std::thread::spawn(move || count_down(&chat, channel, countdowns));
+    Ok(())
+}
+
+fn count_down(chat: &Transport, channel: u64, countdowns: mpsc::Receiver<Countdown>) {
+    let mut pending: Option<Countdown> = None;
+    loop {
+        let countdown = match pending.take() {
+            Some(countdown) => countdown,
+            None => {
+                // checked under the lock, so that no question is sent to a finished timer.
+                let mut timers = match TIMERS.locked() {
+                    Ok(timers) => timers,
+                    Err(err) => return println!("{:?}", err),
+                };
+                match countdowns.try_recv() {
+                    Ok(countdown) => countdown,
+                    Err(_) => {
+                        timers.remove(&channel);
+                        return;
+                    }
+                }
+            }
+        };
+        let timeout = countdown
+            .deadline()
+            .checked_duration_since(Instant::now())
+            .unwrap_or_default();
+        pending = match countdowns.recv_timeout(timeout) {
+            Ok(next) => Some(next),
+            Err(mpsc::RecvTimeoutError::Timeout) => match countdown.warning() {
+                Some(warning) => {
+                    if let Err(err) = warn(chat, channel, countdown.started, warning) {
+                        println!("{:?}", err);
+                    }
+                    Some(Countdown {
+                        warned: true,
+                        ..countdown
+                    })
+                }
+                None => {
+                    if let Err(err) = expire(chat, channel, countdown.started) {
+                        println!("{:?}", err);
+                    }
+                    None
+                }
+            },
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        };
+    }
+}
+
+fn warn(chat: &Transport, channel: u64, started: Instant, warning: u64) -> Result<(), BotError> {
+    if let Some(session) = bot::find_session(channel)? {
+        let events = session.locked()?.warn(started, warning);
+        post(chat, channel, &events)?;
+    }
+    Ok(())
+}
+
+fn expire(chat: &Transport, channel: u64, started: Instant) -> Result<(), BotError> {
+    let session = match bot::find_session(channel)? {
+        Some(session) => session,
+        None => return Ok(()),
+    };
+    let events = session.locked()?.expire(started, &mut rand::thread_rng())?;
+    post(chat, channel, &events)?;
+    drop(session);
+    bot::release(channel)
+}
+
+/// Tells whether a new quiz can be started in the channel, explaining why not otherwise.
+pub(crate) fn standing_by(chat: &Transport, msg: &Message) -> Result<bool, BotError> {
+    let session = bot::session(msg.channel)?;
+    let guard = session.locked()?;
+    Ok(match &guard.quiz {
+        bot::Status::Holding(ref ans, ..) => {
+            try_say!(
+                chat,
+                msg,
+                for_channel(
+                    msg.channel,
+                    msg.guild,
+                    format!("前回の出題が解かれていません\n問題: `{}`", ans.sorted())
+                )?
+            );
+            false
+        }
+        bot::Status::Contesting(ref ans, ..) => {
+            try_say!(
+                chat,
+                msg,
+                for_channel(
+                    msg.channel,
+                    msg.guild,
+                    format!("現在コンテスト中です\n問題: `{}`", ans.sorted())
+                )?
+            );
+            false
+        }
+        bot::Status::StandingBy => true,
+    })
+}
+
+/// Tells whether `lang` may be quizzed in the channel, explaining why not otherwise.
+pub(crate) fn allowed(chat: &Transport, msg: &Message, lang: bot::Lang) -> Result<bool, BotError> {
+    let languages = settings::SETTINGS
+        .locked()?
+        .languages(msg.channel, msg.guild);
+    Ok(match languages {
+        Some(ref languages) if !languages.iter().any(|code| code == lang.as_code()) => {
+            try_say!(
+                chat,
+                msg,
+                format!(
+                    "このチャンネルで出題できる言語は {} です。",
+                    languages.join(", ")
+                )
+            );
+            false
+        }
+        _ => true,
+    })
+}
+
+/// Poses a quiz of `lang`; invoked for the language commands registered in the manifest.
+pub(crate) fn quiz(
+    chat: &Transport,
+    msg: &Message,
+    lang: bot::Lang,
+    args: Vec<String>,
+) -> Result<(), BotError> {
+    println!(
+        "Got command '~{}' by user '{}'",
+        lang.as_command(),
+        msg.author
     );
-    println!("called prob: [{}, {}]", ans, sorted);
-    ans.clone()
+    if_chain! {
+        if !msg.bot;
+        let session = bot::session(msg.channel)?;
+        let mut guard = session.locked()?;
+        then {
+            match parser::quiz(lang.as_command(), args) {
+                Ok(filter) => {
+                    let events = guard.pose(lang, &filter, &mut rand::thread_rng())?;
+                    post(chat, msg.channel, &events)?;
+                }
+                Err(err_msg) => {
+                    try_say!(chat, msg, format!("{}", err_msg));
+                }
+            }
+        }
+    }
+    Ok(())
 }
 
-pub(crate) fn kick(ctx: &mut Context, msg: &Message) -> std::io::Result<()> {
-    use std::process::Command;
-    let mut src = BufWriter::new(File::create("/tmp/main.rs")?);
+/// Longest compiler message or program output posted back to the channel, in characters.
+const MAX_KICK_MESSAGE: usize = 1500;
+
+/// Queues `kick(...);` for the sandbox; the result is posted once it has run.
+pub(crate) fn kick(chat: &Transport, msg: &Message) {
     let code = format!(
         r#"fn kick() {{
     println!("ヒィンｗ");
@@ -50,117 +300,52 @@ fn main() {{
         &msg.content
     );
     println!("{}", code);
-    src.write_all(code.as_bytes())?;
-    src.flush()?;
-    match Command::new("rustc").arg("/tmp/main.rs").output() {
-        Ok(output) => {
-            if output.status.success() {
-                try_say!(ctx, msg, "ヒィンｗ");
-            } else {
-                try_say!(ctx, msg, from_utf8(output.stderr.as_slice()).unwrap());
-            }
-        }
-        Err(e) => {
-            try_say!(ctx, msg, format!("{:?}", e));
-        }
-    }
-    Ok(())
-}
-
-pub(crate) fn answer_check(ctx: &mut Context, msg: &Message) {
-    if let Ok(mut quiz_guard) = bot::QUIZ.lock() {
-        let elapsed = quiz_guard.elapsed();
-        match quiz_guard.answer_check(&msg.content) {
-            bot::CheckResult::WA => {
-                // includes the case that bot is standing by.
-                return;
+    let chat = chat.clone();
+    let channel = msg.channel;
+    sandbox::submit(code, sandbox::Limits::default(), move |result| {
+        let response = match result {
+            Ok(sandbox::Outcome::Success { ref stdout }) if stdout.trim().is_empty() => {
+                "出力はありませんでした。".to_string()
             }
-            bot::CheckResult::Assumed(_ans) => {
-                if quiz_guard.is_holding() {
-                    try_say!(
-                        ctx,
-                        msg,
-                        format!(
-                            "{} さん、正解です！\n正解は\"{}\"でした！ [{:.3} sec]",
-                            &msg.author.name,
-                            quiz_guard.ans().unwrap(),
-                            elapsed.unwrap(),
-                        )
-                    );
-                    *quiz_guard = bot::Status::StandingBy;
-                    return;
-                } else if quiz_guard.is_contesting() {
-                    try_say!(
-                        ctx,
-                        msg,
-                        format!(
-                            "{} さん、正解です！\n正解は\"{}\"でした！ [{:.3} sec]",
-                            &msg.author.name,
-                            quiz_guard.ans().unwrap(),
-                            elapsed.unwrap(),
-                        )
-                    );
-                    let contest_result = &mut *bot::CONTEST_RESULT.lock().unwrap();
-
-                    *contest_result
-                        .entry(msg.author.name.clone())
-                        .or_insert(ContestData::default()) += elapsed.unwrap();
-
-                    let (_, num) = quiz_guard.get_contest_num().unwrap();
-
-                    if quiz_guard.is_contest_end() {
-                        try_say!(
-                            ctx,
-                            msg,
-                            format!(
-                                "{num}問連続のコンテストが終了しました。\n{result}",
-                                num = num,
-                                result = bot::aggregates(dbg!(&*contest_result))
-                            )
-                        );
-                        *contest_result = IndexMap::new();
-                        *quiz_guard = bot::Status::StandingBy;
-                    } else {
-                        quiz_guard.contest_continue(ctx, msg);
-                    }
-                }
+            Ok(sandbox::Outcome::Success { stdout }) => {
+                format!("```\n{}\n```", truncate(&stdout, MAX_KICK_MESSAGE))
             }
-            bot::CheckResult::Anagram(ans) => {
-                if quiz_guard.is_contesting() {
-                    *bot::CONTEST_RESULT
-                        .lock()
-                        .unwrap()
-                        .entry(msg.author.name.clone())
-                        .or_insert(ContestData::default()) += elapsed.unwrap();
-                }
-                try_say!(
-                    ctx,
-                    msg,
-                    format!(
-                        "{} さん、{} は非想定解ですが正解です！",
-                        &msg.author.name,
-                        ans.to_lowercase()
-                    )
-                );
+            Ok(sandbox::Outcome::Failure { stderr, .. }) => {
+                format!("```\n{}\n```", truncate(&stderr, MAX_KICK_MESSAGE))
             }
-            bot::CheckResult::Full(ans) => {
-                if quiz_guard.is_contesting() {
-                    *bot::CONTEST_RESULT
-                        .lock()
-                        .unwrap()
-                        .entry(msg.author.name.clone())
-                        .or_insert(ContestData::default()) += elapsed.unwrap();
-                }
-                try_say!(
-                    ctx,
-                    msg,
-                    format!(
-                        "{} さん、{} は出題辞書にない非想定解ですが正解です！",
-                        &msg.author.name,
-                        ans.to_lowercase()
-                    )
-                );
+            Ok(sandbox::Outcome::TimedOut { stage }) => match stage {
+                sandbox::Stage::Compile => "コンパイルが時間内に終わりませんでした。".to_string(),
+                sandbox::Stage::Run => "実行が時間内に終わりませんでした。".to_string(),
+            },
+            Ok(sandbox::Outcome::Busy) => {
+                "混み合っています。しばらくしてからお試しください。".to_string()
             }
-        }
+            Err(e) => format!("{:?}", e),
+        };
+        chat.say(channel, response);
+    });
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((pos, _)) => format!("{}\n...", &text[..pos]),
+        None => text.to_string(),
     }
 }
+
+pub(crate) fn answer_check(chat: &Transport, msg: &Message) -> Result<(), BotError> {
+    // most messages are chat in channels that never played; they get no session.
+    let session = match bot::find_session(msg.channel)? {
+        Some(session) => session,
+        None => return Ok(()),
+    };
+    let mut guard = session.locked()?;
+    // includes the case that bot is standing by.
+    let events = guard.answer(
+        msg.author_id,
+        &msg.author,
+        &msg.content,
+        &mut rand::thread_rng(),
+    )?;
+    post(chat, msg.channel, &events)
+}
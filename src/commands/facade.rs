@@ -1,391 +1,406 @@
-use regex::Regex;
-use serenity::{
-    framework::standard::{
-        macros::{command, group, help},
-        Args, CommandResult,
-    },
-    model::channel::Message,
-    prelude::*,
-};
-use std::time::Instant;
-
 use super::super::bot;
+use super::super::chat::{Message, Transport};
+use super::super::error::{BotError, Lock};
+use super::super::history;
+use super::super::language::LANGUAGES;
+use super::super::rating;
 
-use super::super::settings;
-use super::super::sort::Sorted;
+use super::super::settings::permission::Level;
+use super::super::settings::scope::Source;
+use super::super::settings::{self, sync_setting};
 use super::{executors, parser};
-use crate::bot::ContestData;
 use crate::try_say;
-use indexmap::IndexMap;
-use serenity::framework::standard::{help_commands, CommandGroup, HelpOptions};
-use serenity::model::id::UserId;
-use std::collections::HashSet;
-use std::fs::OpenOptions;
-use std::io::Write;
-use unicode_segmentation::UnicodeSegmentation;
 
-macro_rules! count {
-    ( $x:ident ) => (1usize);
-    ( $x:ident, $($xs:tt)* ) => (1usize + count!($($xs)*));
+pub type CommandResult = anyhow::Result<()>;
+
+/// Rate limit of a command per user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bucket {
+    /// Once a second.
+    Basic,
+    /// Once in two seconds.
+    Long,
 }
 
-macro_rules! quiz_commands {
-    () => {};
-    ( $command:ident, $( $commands:ident ),* ) => {
-        group!({
-            name: "quiz",
-            options: {
-                description: "A group with commands providing a quiz with specific language as response.",
-            },
-            commands: [$command, $($commands),*],
-        });
-        const COMMAND_NUM: usize = count!($($commands),*) + 1;
-        lazy_static! {
-            pub static ref QUIZ_COMMANDS: [String; COMMAND_NUM] = [stringify!($command).to_string(), $(stringify!($commands).to_string(),)*];
-            pub static ref QUIZ_COMMANDS_REGEX: Regex = Regex::new(
-                &vec!["^(contest|", stringify!($command), $("|", stringify!($commands),)* ")$"].join("")
-            ).unwrap();
+impl Bucket {
+    pub fn interval(self) -> chrono::Duration {
+        match self {
+            Bucket::Basic => chrono::Duration::seconds(1),
+            Bucket::Long => chrono::Duration::seconds(2),
+        }
+    }
+}
+
+pub struct Command {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub bucket: Bucket,
+    /// Checked by the router before the command runs.
+    pub permission: Level,
+    /// Runs with the level of the author, which is at least `permission`.
+    pub run: fn(&Transport, &Message, Level, Vec<String>) -> CommandResult,
+}
+
+pub struct Group {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub commands: &'static [Command],
+}
+
+macro_rules! command {
+    ($name: ident, $bucket: ident, $description: expr) => {
+        command!($name, $bucket, $description, Everyone)
+    };
+    ($name: ident, $bucket: ident, $description: expr, $permission: ident) => {
+        Command {
+            name: stringify!($name),
+            description: $description,
+            bucket: Bucket::$bucket,
+            permission: Level::$permission,
+            run: $name,
         }
     };
 }
 
-quiz_commands!(en, ja, fr, de, it, ru, eo);
-/*
-quiz_commands! {
-    en: {
-        dictionary = english,
-    },
-    ja: {
-        dictionary = japanese,
-    },
-    fr: {
-        dictionary = french,
+pub static GROUPS: &[Group] = &[
+    Group {
+        name: "quiz",
+        description: "A group with commands providing a quiz with specific language as response. Each language registered in `languages.toml` is a command, e.g. `~en`.",
+        commands: &[command!(languages, Basic, "Lists the quiz languages.")],
     },
-    de: {
-        dictionary = german,
-    },
-};
-*/
-group!({
-    name: "extra",
-    options: {
+    Group {
+        name: "extra",
         description: "A group with commands providing hint and giveup.",
+        commands: &[
+            command!(
+                giveup,
+                Basic,
+                "Allows to give up current quiz and shows answer as response."
+            ),
+            command!(hint, Long, "Gives hint as response."),
+        ],
     },
-    commands: [giveup, hint],
-});
-
-group!({
-    name: "contest",
-    options: {
+    Group {
+        name: "contest",
         description: "A group with commands providing contest mode.",
+        commands: &[
+            command!(contest, Long, "Starts contest mode."),
+            command!(
+                unrated,
+                Long,
+                "Force closes current contest (moderators only).",
+                Moderator
+            ),
+            command!(
+                history,
+                Long,
+                "Shows recent contests in the channel, or the details of a past contest."
+            ),
+            command!(
+                rating,
+                Long,
+                "Shows the rating of a user (yourself by default)."
+            ),
+            command!(ranking, Long, "Shows the rating ranking."),
+        ],
     },
-    commands: [contest, unrated],
-});
-
-group!({
-    name: "settings",
-    options: {
+    Group {
+        name: "settings",
         description: "A group with commands providing settings of enable/disable switch in channel.",
+        commands: &[
+            command!(
+                enable,
+                Long,
+                "Enable nazonazo bot on a channel (moderators only).",
+                Moderator
+            ),
+            command!(
+                disable,
+                Long,
+                "Disable nazonazo bot on a channel (moderators only).",
+                Moderator
+            ),
+            command!(
+                prefix,
+                Long,
+                "Set prefix on a channel (moderators only).",
+                Moderator
+            ),
+            command!(
+                timeout,
+                Long,
+                "Set time limit of questions on a channel (moderators only).",
+                Moderator
+            ),
+            command!(
+                config,
+                Long,
+                "Shows the settings of a channel and where they come from, or changes them for the channel or its guild (moderators only; guild settings and moderator roles need administrators).",
+                Moderator
+            ),
+            command!(
+                reload,
+                Long,
+                "Reloads dictionaries from disk (owner only).",
+                Owner
+            ),
+        ],
     },
-    commands: [enable, disable, prefix],
-});
+];
 
-#[command]
-#[description = "Provides a quiz of English as response."]
-#[bucket = "basic"]
-pub fn en(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~en' by user '{}'", msg.author.name);
-    if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut guard) = bot::QUIZ.lock();
-        then {
-            let ans = executors::prob(ctx, &msg, bot::Lang::En);
-            *guard = bot::Status::Holding(ans, bot::Lang::En, Instant::now());
-        }
-    }
-    Ok(())
+/// Looks up a command by its name.
+pub fn find(name: &str) -> Option<&'static Command> {
+    GROUPS
+        .iter()
+        .flat_map(|group| group.commands.iter())
+        .find(|command| command.name == name)
 }
 
-#[command]
-#[description = "Provides a quiz of Japanese as response."]
-#[bucket = "basic"]
-pub fn ja(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~ja' by user '{}'", msg.author.name);
-    if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut guard) = bot::QUIZ.lock();
-        then {
-            let ans = executors::prob(ctx, &msg, bot::Lang::Ja);
-            *guard = bot::Status::Holding(ans, bot::Lang::Ja, Instant::now());
-        }
-    }
-    Ok(())
+/// Lists the groups and their commands.
+pub fn help(prefix: &str) -> String {
+    GROUPS
+        .iter()
+        .map(|group| {
+            format!(
+                "**{}**: {}\n{}",
+                group.name,
+                group.description,
+                group
+                    .commands
+                    .iter()
+                    .map(|command| format!(
+                        "`{}{}`: {}\n",
+                        prefix, command.name, command.description
+                    ))
+                    .collect::<String>()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
-#[command]
-#[description = "Provides a quiz of French as response."]
-#[bucket = "basic"]
-pub fn fr(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~fr' by user '{}'", msg.author.name);
-    if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut guard) = bot::QUIZ.lock();
-        then {
-            let ans = executors::prob(ctx, &msg, bot::Lang::Fr);
-            *guard = bot::Status::Holding(ans, bot::Lang::Fr, Instant::now());
-        }
-    }
+
+pub fn languages(
+    chat: &Transport,
+    msg: &Message,
+    _level: Level,
+    _args: Vec<String>,
+) -> CommandResult {
+    println!("Got command '~languages' by user '{}'", msg.author);
+    let languages = LANGUAGES
+        .langs()
+        .map(|lang| {
+            let language = LANGUAGES.get(lang);
+            Ok(format!(
+                "`{}`: {} (contest: `{}`, len = {})",
+                language.command,
+                language.symbol,
+                language.code,
+                lang.dictionary()?.len()
+            ))
+        })
+        .collect::<Result<Vec<_>, BotError>>()?;
+    try_say!(chat, msg, languages.join("\n"));
     Ok(())
 }
-#[command]
-#[description = "Provides a quiz of German as response."]
-#[bucket = "basic"]
-pub fn de(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~de' by user '{}'", msg.author.name);
+
+pub fn giveup(chat: &Transport, msg: &Message, _level: Level, _args: Vec<String>) -> CommandResult {
+    println!("Got command '~giveup' by user '{}'", msg.author);
+    let session = bot::session(msg.channel)?;
     if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut guard) = bot::QUIZ.lock();
+        if !msg.bot;
+        let mut guard = session.locked()?;
         then {
-            let ans = executors::prob(ctx, &msg, bot::Lang::De);
-            *guard = bot::Status::Holding(ans, bot::Lang::De, Instant::now());
+            println!("giveup is accepted");
+            let events = guard.giveup(&mut rand::thread_rng())?;
+            executors::post(chat, msg.channel, &events)?;
         }
     }
     Ok(())
 }
-#[command]
-#[description = "Provides a quiz of Italian as response."]
-#[bucket = "basic"]
-pub fn it(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~it' by user '{}'", msg.author.name);
+
+pub fn contest(chat: &Transport, msg: &Message, _level: Level, args: Vec<String>) -> CommandResult {
+    println!("Got command '~contest' by user '{}'", msg.author);
+    let session = bot::session(msg.channel)?;
     if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut guard) = bot::QUIZ.lock();
+        if !msg.bot;
+        let mut guard = session.locked()?;
+        if guard.quiz.is_standing_by();
         then {
-            let ans = executors::prob(ctx, &msg, bot::Lang::It);
-            *guard = bot::Status::Holding(ans, bot::Lang::It, Instant::now());
+            match parser::contest(args) {
+                Err(err_msg) => {
+                    try_say!(chat,msg,err_msg);
+                    return Ok(());
+                }
+                Ok((num, mut languages, filter)) => {
+                    let allowed = settings::SETTINGS.locked()?.languages(msg.channel, msg.guild);
+                    if let Some(allowed) = allowed {
+                        if languages.is_empty() {
+                            languages = allowed;
+                        } else if let Some(lang) = languages.iter().find(|lang| !allowed.contains(lang)) {
+                            try_say!(
+                                chat,
+                                msg,
+                                format!("`{}` はこのチャンネルでは出題できません。出題できる言語: {}", lang, allowed.join(", "))
+                            );
+                            return Ok(());
+                        }
+                    } else if languages.is_empty() {
+                        languages = LANGUAGES.codes();
+                    }
+                    languages.sort();
+                    languages.dedup();
+                    let events = guard.start_contest(num, &languages, filter, &mut rand::thread_rng())?;
+                    executors::post(chat, msg.channel, &events)?;
+                }
+            }
         }
     }
-    Ok(())
-}
-#[command]
-#[description = "Provides a quiz of Russian as response."]
-#[bucket = "basic"]
-pub fn ru(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~ru' by user '{}'", msg.author.name);
-    if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut guard) = bot::QUIZ.lock();
-        then {
-            let ans = executors::prob(ctx, &msg, bot::Lang::Ru);
-            *guard = bot::Status::Holding(ans, bot::Lang::Ru, Instant::now());
-        }
+    let mut guard = session.locked()?;
+    if guard.quiz.is_standing_by() && !guard.contest_result.is_empty() {
+        guard.contest_result.clear();
+        return Err(BotError::ContestState.into());
     }
     Ok(())
 }
-#[command]
-#[description = "Provides a quiz of Esperanto as response."]
-#[bucket = "basic"]
-pub fn eo(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~eo' by user '{}'", msg.author.name);
-    if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut guard) = bot::QUIZ.lock();
-        then {
-            let ans = executors::prob(ctx, &msg, bot::Lang::Eo);
-            *guard = bot::Status::Holding(ans, bot::Lang::Eo, Instant::now());
-        }
-    }
+
+pub fn unrated(
+    chat: &Transport,
+    msg: &Message,
+    _level: Level,
+    _args: Vec<String>,
+) -> CommandResult {
+    println!("Got command '~unrated' by user '{}'", msg.author);
+    let session = bot::session(msg.channel)?;
+    let mut guard = session.locked()?;
+    let events = guard.abort();
+    executors::post(chat, msg.channel, &events)?;
     Ok(())
 }
 
-fn giveup_impl(ctx: &mut Context, msg: &Message, quiz_stat: &mut bot::Status) -> CommandResult {
-    if !msg.author.bot {
-        if quiz_stat.is_standing_by() {
-            try_say!(ctx, msg, "現在問題は出ていません。");
-        } else if quiz_stat.is_holding() {
-            try_say!(
-                ctx,
-                msg,
-                format!("正解は \"{}\" でした...", quiz_stat.ans().unwrap())
-            );
-            *quiz_stat = bot::Status::StandingBy;
-        } else {
-            let contest_result = &mut *bot::CONTEST_RESULT.lock().unwrap();
-            *contest_result
-                .entry("~giveup".to_string())
-                .or_insert(ContestData::default()) += quiz_stat.elapsed().unwrap();
-            if !quiz_stat.is_contest_end() {
-                try_say!(
-                    ctx,
-                    msg,
-                    format!("正解は \"{}\" でした...", quiz_stat.ans().unwrap())
-                );
-                quiz_stat.contest_continue(ctx, &msg);
+pub fn history(chat: &Transport, msg: &Message, _level: Level, args: Vec<String>) -> CommandResult {
+    println!("Got command '~history' by user '{}'", msg.author);
+    let channel = msg.channel;
+    match parser::history(args) {
+        Ok(None) => {
+            let history = history::HISTORY.locked()?;
+            let recent = history
+                .of_channel(channel)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .take(10)
+                .map(|(id, record)| record.summary(id))
+                .collect::<Vec<_>>();
+            if recent.is_empty() {
+                try_say!(chat, msg, "このチャンネルのコンテスト履歴はありません。");
             } else {
-                let (_, num) = quiz_stat.get_contest_num().unwrap();
-                msg.channel_id
-                    .say(
-                        &ctx,
-                        format!(
-                            "正解は \"{ans}\" でした...\n{num}問連続のコンテストが終了しました。\n{result}",
-                            ans = quiz_stat.ans().unwrap(),
-                            num = num,
-                            result = bot::aggregates(contest_result)
-                        ),
-                    )
-                    .expect("fail to post");
-                *contest_result = IndexMap::new();
-                *quiz_stat = bot::Status::StandingBy;
+                try_say!(chat, msg, recent.join("\n"));
             }
         }
+        Ok(Some(id)) => {
+            let history = history::HISTORY.locked()?;
+            let found = history.of_channel(channel).find(|(index, _)| *index == id);
+            match found {
+                Some((id, record)) => {
+                    try_say!(chat, msg, record.details(id));
+                }
+                None => {
+                    try_say!(chat, msg, format!("コンテスト #{} は見つかりません。", id));
+                }
+            }
+        }
+        Err(err_msg) => {
+            try_say!(chat, msg, format!("{}", err_msg));
+        }
     }
     Ok(())
 }
 
-#[command]
-#[description = "Allows to give up current quiz and shows answer as response."]
-#[bucket = "basic"]
-pub fn giveup(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~giveup' by user '{}'", msg.author.name);
-    if let Ok(mut guard) = bot::QUIZ.lock() {
-        println!("giveup is accepted");
-        giveup_impl(ctx, msg, &mut *guard)?;
-    }
-    Ok(())
-}
-
-#[command]
-#[description = "Starts contest mode."]
-#[bucket = "long"]
-pub fn contest(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
-    use crate::bot::CONTEST_LIBRARY;
-    println!("Got command '~contest' by user '{}'", msg.author.name);
-    if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut quiz_guard) = bot::QUIZ.lock();
-        if quiz_guard.is_standing_by();
-        then {
-            match parser::contest(&mut args) {
-                Err(err_msg) => {
-                    try_say!(ctx,msg,err_msg);
-                    return Ok(());
+pub fn rating(chat: &Transport, msg: &Message, _level: Level, args: Vec<String>) -> CommandResult {
+    println!("Got command '~rating' by user '{}'", msg.author);
+    match parser::rating(args) {
+        Ok(user) => {
+            let ratings = rating::RATINGS.locked()?;
+            let (name, player) = match user {
+                Some(name) => {
+                    let player = ratings.find(&name);
+                    (name, player)
                 }
-                Ok((num, mut languages)) => {
-                    languages.sort();
-                    languages.dedup();
-                    CONTEST_LIBRARY.lock().unwrap().init(languages);
-                    let (dic, lang) = CONTEST_LIBRARY
-                        .lock()
-                        .unwrap()
-                        .select(&mut rand::thread_rng());
-                    let ans = dic.get(&mut rand::thread_rng());
-                    msg.channel_id
-                        .say(
-                            &ctx,
-                            format!(
-                                "{number}問のコンテストを始めます。\n問 1 (1/{number})\nソートなぞなぞ ソート前の {symbol} な〜んだ？\n`{prob}`",
-                                number = num,
-                                prob = ans.sorted(),
-                                symbol = lang.as_symbol(),
-                            ),
+                None => (msg.author.clone(), ratings.get(msg.author_id)),
+            };
+            match player {
+                Some(player) => {
+                    try_say!(
+                        chat,
+                        msg,
+                        format!(
+                            "{} さんのレートは {:.0} です。({}回参加)",
+                            name, player.rating, player.contests
                         )
-                        .expect("fail to post");
-                    *bot::CONTEST_RESULT.lock().unwrap() = IndexMap::new();
-                    *quiz_guard = bot::Status::Contesting(ans.to_string(), lang, (1, num), Instant::now());
+                    );
+                }
+                None => {
+                    try_say!(
+                        chat,
+                        msg,
+                        format!("{} さんはまだレートがありません。", name)
+                    );
                 }
             }
         }
-    }
-    if !bot::CONTEST_RESULT.lock().unwrap().is_empty() {
-        panic!("contest result: initialization error");
+        Err(err_msg) => {
+            try_say!(chat, msg, format!("{}", err_msg));
+        }
     }
     Ok(())
 }
 
-#[command]
-#[description = "Force closes current contest."]
-#[bucket = "long"]
-pub fn unrated(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~unrated' by user '{}'", msg.author.name);
-    loop {
-        if let (Ok(mut quiz), Ok(mut result)) = (bot::QUIZ.lock(), bot::CONTEST_RESULT.lock()) {
-            if quiz.is_contesting() {
-                try_say!(ctx, msg, "コンテストを中止します。");
-                *quiz = bot::Status::StandingBy;
-                *result = IndexMap::new();
-            } else {
-                try_say!(ctx, msg, "現在コンテストは開催されていません。");
-            }
-            break;
-        }
+pub fn ranking(
+    chat: &Transport,
+    msg: &Message,
+    _level: Level,
+    _args: Vec<String>,
+) -> CommandResult {
+    use ordinal::Ordinal;
+    println!("Got command '~ranking' by user '{}'", msg.author);
+    let ratings = rating::RATINGS.locked()?;
+    let ranking = ratings.ranking();
+    if ranking.is_empty() {
+        try_say!(chat, msg, "まだレート対象のコンテストがありません。");
+    } else {
+        try_say!(
+            chat,
+            msg,
+            ranking
+                .into_iter()
+                .take(10)
+                .enumerate()
+                .map(|(index, player)| format!(
+                    "{}: {}, {:.0}\n",
+                    Ordinal(index + 1).to_string(),
+                    player.name,
+                    player.rating
+                ))
+                .collect::<String>()
+        );
     }
     Ok(())
 }
 
-#[command]
-#[description = "Gives hint as response."]
-#[bucket = "long"]
-pub fn hint(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
-    println!("Got command '~hint' by user '{}'", msg.author.name);
+pub fn hint(chat: &Transport, msg: &Message, _level: Level, args: Vec<String>) -> CommandResult {
+    println!("Got command '~hint' by user '{}'", msg.author);
     if_chain! {
-        if !msg.author.bot;
-        if let Ok(mut guard) = bot::QUIZ.lock();
-        if !guard.is_standing_by();
+        if !msg.bot;
+        let session = bot::session(msg.channel)?;
+        let mut guard = session.locked()?;
         then {
-            let mut g = UnicodeSegmentation::graphemes(guard.ans().unwrap().as_str(), true).collect::<Vec<&str>>();
-            match parser::hint(&mut args) {
+            match parser::hint(args) {
                 Err(err_msg) => {
-                    try_say!(ctx,msg,format!("{}", err_msg));
-                },
-                Ok(parser::Hint::First(num)) | Ok(parser::Hint::Random(num)) if num == 0 => {
-                    try_say!(ctx,msg,"ゼロ文字ヒントはだせません。");
-                },
-                Ok(parser::Hint::First(num)) | Ok(parser::Hint::Random(num)) if num == g.len() || num == g.len() - 1 => {
-                    try_say!(ctx,msg,"答えが一意に定まるためギブアップとみなされました！");
-                    giveup_impl(ctx, msg, &mut *guard)?;
-                },
-                Ok(parser::Hint::First(num)) | Ok(parser::Hint::Random(num)) if num > g.len() => {
-                    try_say!(ctx,msg,"ヒントが文字数を超えていますｗ");
+                    try_say!(chat,msg,format!("{}", err_msg));
                 },
-                Ok(parser::Hint::First(num)) => {
-                    g.truncate(num);
-                    msg.channel_id
-                        .say(
-                            &ctx,
-                            format!(
-                                "答えの先頭 {len} 文字は... => `{hint}` ",
-                                len = num,
-                                hint = g.into_iter().collect::<String>(),
-                            ),
-                        )
-                        .expect("fail to post");
-                },
-                Ok(parser::Hint::Random(num)) => {
-                    let star = "*";
-                    let mut hit_str: Vec<&str> = std::iter::repeat(star).take(g.len()).collect();
-                    for idx in rand::seq::index::sample(&mut rand::thread_rng(), g.len(), num).into_iter() {
-                        if let Some(elem) = hit_str.get_mut(idx) {
-                            *elem = g.get(idx).unwrap();
-                        }
-                    }
-                    msg.channel_id
-                        .say(
-                            &ctx,
-                            format!(
-                                "ランダムヒント {len} 文字... => `{hint}` ",
-                                len = num,
-                                hint = hit_str.join(""),
-                            ),
-                        )
-                        .expect("fail to post");
-                },
-            }
-        } else {
-            match parser::hint(&mut args) {
-                Err(err_msg) => {
-                    try_say!(ctx,msg,format!("{}", err_msg));
-                },
-                Ok(_) => {
-                    try_say!(ctx,msg,"問題が出てないですよ？");
+                Ok(kind) => {
+                    let events = guard.hint(kind, &mut rand::thread_rng())?;
+                    executors::post(chat, msg.channel, &events)?;
                 },
             }
         }
@@ -393,125 +408,77 @@ pub fn hint(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
     Ok(())
 }
 
-#[help]
-fn nazonazo_help(
-    context: &mut Context,
-    msg: &Message,
-    args: Args,
-    help_options: &'static HelpOptions,
-    groups: &[&'static CommandGroup],
-    owners: HashSet<UserId>,
-) -> CommandResult {
-    help_commands::with_embeds(context, msg, args, help_options, groups, owners)
-}
-
-fn sync_setting() -> anyhow::Result<()> {
-    use anyhow::Context as _;
-
-    let path = std::path::Path::new("/tmp/settings/settings.toml");
-    let mut conf = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .open(path)
-        .with_context(|| path.to_string_lossy())?;
-    conf.write_all(
-        toml::to_string(&*settings::SETTINGS.lock().unwrap())
-            .context("/tmp/settings/settings.toml")?
-            .as_bytes(),
-    )
-    .with_context(|| path.to_string_lossy())?;
-    conf.sync_all().with_context(|| path.to_string_lossy())?;
-    Ok(())
-}
-
-#[command]
-#[description = "Enable nazonazo bot on a channel."]
-#[bucket = "long"]
-pub fn enable(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~enable' by user '{}'", msg.author.name);
-    if !settings::SETTINGS
-        .lock()
-        .unwrap()
-        .channel
-        .enabled
-        .contains(&*msg.channel_id.as_u64())
-    {
-        settings::SETTINGS
-            .lock()
-            .unwrap()
-            .channel
-            .enabled
-            .push(*msg.channel_id.as_u64());
+pub fn enable(chat: &Transport, msg: &Message, _level: Level, _args: Vec<String>) -> CommandResult {
+    println!("Got command '~enable' by user '{}'", msg.author);
+    let enabled = {
+        let mut settings = settings::SETTINGS.locked()?;
+        let enabled = settings.is_enabled(msg.channel, msg.guild);
+        if !enabled {
+            settings.channel_mut(msg.channel, msg.guild).enabled = Some(true);
+        }
+        enabled
+    };
+    if !enabled {
         try_say!(
-            ctx,
+            chat,
             msg,
             "このチャンネルでソートなぞなぞが有効になりました。"
         );
         Ok(sync_setting()?)
     } else {
-        try_say!(ctx, msg, "このチャンネルでソートなぞなぞはすでに有効です。");
+        try_say!(
+            chat,
+            msg,
+            "このチャンネルでソートなぞなぞはすでに有効です。"
+        );
         Ok(())
     }
 }
 
-#[command]
-#[description = "Disable nazonazo bot on a channel."]
-#[bucket = "long"]
-pub fn disable(ctx: &mut Context, msg: &Message) -> CommandResult {
-    println!("Got command '~disable' by user '{}'", msg.author.name);
+pub fn disable(
+    chat: &Transport,
+    msg: &Message,
+    _level: Level,
+    _args: Vec<String>,
+) -> CommandResult {
+    println!("Got command '~disable' by user '{}'", msg.author);
     settings::SETTINGS
-        .lock()
-        .unwrap()
-        .channel
-        .enabled
-        .retain(|id| *id != *msg.channel_id.as_u64());
+        .locked()?
+        .channel_mut(msg.channel, msg.guild)
+        .enabled = Some(false);
     try_say!(
-        ctx,
+        chat,
         msg,
         "このチャンネルでソートなぞなぞが無効になりました。"
     );
     Ok(sync_setting()?)
 }
 
-#[command]
-#[description = "Set prefix on a channel."]
-#[bucket = "long"]
-pub fn prefix(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
-    println!("Got command '~prefix' by user '{}'", msg.author.name);
-    match parser::prefix(&mut args) {
+pub fn prefix(chat: &Transport, msg: &Message, _level: Level, args: Vec<String>) -> CommandResult {
+    println!("Got command '~prefix' by user '{}'", msg.author);
+    match parser::prefix(args) {
         Ok(Some(prefix)) => {
             settings::SETTINGS
-                .lock()
-                .unwrap()
-                .prefix
-                .dynamic
-                .entry(msg.channel_id.as_u64().to_string())
-                .and_modify(|old| *old = prefix.clone())
-                .or_insert(prefix.clone());
+                .locked()?
+                .channel_mut(msg.channel, msg.guild)
+                .prefix = Some(prefix.clone());
             try_say!(
-                ctx,
+                chat,
                 msg,
                 format!("このチャンネルでprefixが `{}` になりました。", prefix)
             );
         }
         Ok(None) => {
-            let prefix = settings::SETTINGS
-                .lock()
-                .unwrap()
-                .prefix
-                .dynamic
-                .get(&msg.channel_id.as_u64().to_string())
-                .cloned()
-                .unwrap_or_else(|| "~".to_string());
+            let prefix = settings::SETTINGS.locked()?.prefix(msg.channel, msg.guild);
 
             try_say!(
-                ctx,
+                chat,
                 msg,
                 format!("このチャンネルのprefixは現在 `{}` です。", prefix)
             );
         }
         Err(err_msg) => {
-            try_say!(ctx, msg, format!("{}", err_msg));
+            try_say!(chat, msg, format!("{}", err_msg));
         }
     }
     if let Err(err) = sync_setting() {
@@ -519,3 +486,218 @@ pub fn prefix(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult
     }
     Ok(())
 }
+
+pub fn timeout(chat: &Transport, msg: &Message, _level: Level, args: Vec<String>) -> CommandResult {
+    println!("Got command '~timeout' by user '{}'", msg.author);
+    match parser::timeout(args) {
+        Ok(parser::Timeout::Set(seconds, warning)) => {
+            settings::SETTINGS
+                .locked()?
+                .channel_mut(msg.channel, msg.guild)
+                .timeout = Some(settings::timeout::Limit { seconds, warning });
+            try_say!(
+                chat,
+                msg,
+                format!("このチャンネルの制限時間が {} 秒になりました。", seconds)
+            );
+        }
+        Ok(parser::Timeout::Off) => {
+            settings::SETTINGS
+                .locked()?
+                .channel_mut(msg.channel, msg.guild)
+                .timeout = Some(settings::timeout::Limit::OFF);
+            try_say!(chat, msg, "このチャンネルの制限時間を解除しました。");
+        }
+        Ok(parser::Timeout::Show) => {
+            let limit = settings::SETTINGS.locked()?.timeout(msg.channel, msg.guild);
+            match limit {
+                Some(limit) => {
+                    try_say!(
+                        chat,
+                        msg,
+                        format!("このチャンネルの制限時間は現在 {} 秒です。", limit.seconds)
+                    );
+                }
+                None => {
+                    try_say!(chat, msg, "このチャンネルに制限時間はありません。");
+                }
+            }
+        }
+        Err(err_msg) => {
+            try_say!(chat, msg, format!("{}", err_msg));
+        }
+    }
+    Ok(sync_setting()?)
+}
+
+fn source_label(source: Source) -> &'static str {
+    match source {
+        Source::Channel => "チャンネル",
+        Source::Guild => "サーバー",
+        Source::Default => "既定",
+    }
+}
+
+/// Effective settings of the channel, each with where it comes from.
+fn describe(config: &settings::Config, channel: u64, guild: Option<u64>) -> String {
+    let (enabled, enabled_from) = config.lookup(channel, guild, |options| options.enabled);
+    let (prefix, prefix_from) = config.lookup(channel, guild, |options| options.prefix.clone());
+    let (languages, languages_from) =
+        config.lookup(channel, guild, |options| options.languages.clone());
+    let (timeout, timeout_from) = config.lookup(channel, guild, |options| options.timeout);
+    let (moderators, moderators_from) =
+        config.lookup(channel, guild, |options| options.moderators.clone());
+    let (x_system, x_system_from) = config.lookup(channel, guild, |options| options.x_system);
+    format!(
+        "enabled: `{}` ({})\nprefix: `{}` ({})\nlanguages: {} ({})\ntimeout: {} ({})\nmoderators: {} ({})\nx_system: `{}` ({})",
+        if enabled.unwrap_or(false) {
+            "on"
+        } else {
+            "off"
+        },
+        source_label(enabled_from),
+        prefix.unwrap_or_else(|| settings::DEFAULT_PREFIX.to_string()),
+        source_label(prefix_from),
+        languages.map_or_else(
+            || "すべて".to_string(),
+            |languages| format!("`{}`", languages.join(", "))
+        ),
+        source_label(languages_from),
+        match timeout.filter(|limit| !limit.is_off()) {
+            Some(settings::timeout::Limit {
+                seconds,
+                warning: Some(warning),
+            }) => format!("{} 秒 (残り {} 秒で通知)", seconds, warning),
+            Some(limit) => format!("{} 秒", limit.seconds),
+            None => "なし".to_string(),
+        },
+        source_label(timeout_from),
+        moderators
+            .filter(|roles| !roles.is_empty())
+            .map_or_else(
+                || "なし".to_string(),
+                |roles| {
+                    roles
+                        .iter()
+                        .map(|role| format!("<@&{}>", role))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }
+            ),
+        source_label(moderators_from),
+        if x_system.unwrap_or(false) {
+            "on"
+        } else {
+            "off"
+        },
+        source_label(x_system_from),
+    )
+}
+
+pub fn config(chat: &Transport, msg: &Message, level: Level, args: Vec<String>) -> CommandResult {
+    println!("Got command '~config' by user '{}'", msg.author);
+    let (guild, change) = match parser::config(args) {
+        Ok(parser::Config::Show) => {
+            let description = describe(&*settings::SETTINGS.locked()?, msg.channel, msg.guild);
+            try_say!(chat, msg, format!("このチャンネルの設定:\n{}", description));
+            return Ok(());
+        }
+        Ok(parser::Config::Set { setting, guild }) => (guild, Ok(setting)),
+        Ok(parser::Config::Unset { key, guild }) => (guild, Err(key)),
+        Err(err_msg) => {
+            try_say!(chat, msg, format!("{}", err_msg));
+            return Ok(());
+        }
+    };
+    let scope = match (guild, msg.guild) {
+        (false, _) => "このチャンネル",
+        (true, Some(_)) => "このサーバー",
+        (true, None) => {
+            try_say!(
+                chat,
+                msg,
+                "`--guild` はサーバーのチャンネルでのみ使えます。"
+            );
+            return Ok(());
+        }
+    };
+    let moderators = match &change {
+        Ok(parser::Setting::Moderators(_)) => true,
+        Ok(_) => false,
+        Err(key) => key == "moderators",
+    };
+    // moderators could otherwise appoint themselves or change the whole guild.
+    if (guild || moderators) && level < Level::Administrator {
+        try_say!(
+            chat,
+            msg,
+            "サーバーの設定とモデレーターのロールは管理者のみ変更できます。"
+        );
+        return Ok(());
+    }
+    let description = {
+        let mut config = settings::SETTINGS.locked()?;
+        let options = match msg.guild.filter(|_| guild) {
+            Some(guild) => config.guild_mut(guild),
+            None => config.channel_mut(msg.channel, msg.guild),
+        };
+        match change {
+            Ok(parser::Setting::Enabled(enabled)) => options.enabled = Some(enabled),
+            Ok(parser::Setting::Prefix(prefix)) => options.prefix = Some(prefix),
+            Ok(parser::Setting::Languages(mut languages)) => {
+                languages.sort();
+                languages.dedup();
+                options.languages = Some(languages);
+            }
+            Ok(parser::Setting::Timeout(seconds, warning)) => {
+                options.timeout = Some(settings::timeout::Limit { seconds, warning })
+            }
+            Ok(parser::Setting::Moderators(roles)) => options.moderators = Some(roles),
+            Ok(parser::Setting::XSystem(x_system)) => options.x_system = Some(x_system),
+            Err(key) => match key.as_str() {
+                "enabled" => options.enabled = None,
+                "prefix" => options.prefix = None,
+                "languages" => options.languages = None,
+                "moderators" => options.moderators = None,
+                "x_system" => options.x_system = None,
+                _ => options.timeout = None,
+            },
+        }
+        describe(&config, msg.channel, msg.guild)
+    };
+    try_say!(
+        chat,
+        msg,
+        format!("{}の設定を変更しました。\n{}", scope, description)
+    );
+    Ok(sync_setting()?)
+}
+
+pub fn reload(chat: &Transport, msg: &Message, _level: Level, args: Vec<String>) -> CommandResult {
+    println!("Got command '~reload' by user '{}'", msg.author);
+    match parser::reload(args) {
+        Ok(language) => {
+            let languages = language.map_or_else(
+                || Ok(LANGUAGES.langs().collect::<Vec<_>>()),
+                |lang| lang.parse::<bot::Lang>().map(|lang| vec![lang]),
+            )?;
+            let report = languages
+                .into_iter()
+                .map(|lang| match lang.reload() {
+                    Ok(len) => format!("{}: reloaded (len = {})", lang.as_code(), len),
+                    Err(err) => format!(
+                        "{}: failed to reload, keeping the old dictionary ({})",
+                        lang.as_code(),
+                        err
+                    ),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            try_say!(chat, msg, report);
+        }
+        Err(err_msg) => {
+            try_say!(chat, msg, format!("{}", err_msg));
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,8 @@
-use super::facade;
+use crate::bot::Hint;
+use crate::dictionary::{Difficulty, Filter};
+use crate::language::LANGUAGES;
 use boolinator::Boolinator;
-use clap::{App, AppSettings, Arg, SubCommand};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
 fn range_validator(low: u32, up: u32) -> Box<dyn Fn(String) -> Result<(), String>> {
     Box::new(move |num: String| match num.parse::<u32>() {
@@ -20,7 +22,7 @@ fn parse_validator<T: std::str::FromStr>(num: String) -> Result<(), String> {
 }
 
 fn language_validator(language: String) -> Result<(), String> {
-    if !facade::QUIZ_COMMANDS_REGEX.is_match(&language) {
+    if LANGUAGES.find(&language).is_none() {
         Err(format!("unexpected language '{}'.", language))
     } else {
         Ok(())
@@ -34,9 +36,70 @@ fn prefix_validator(prefix: String) -> Result<(), String> {
     )
 }
 
-pub(crate) fn contest(
-    args: &mut serenity::framework::standard::Args,
-) -> clap::Result<(u32, Vec<String>)> {
+/// Parses `5..8` (inclusive) or a single length `5`.
+fn parse_len(len: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("`{}` is invalid. e.g. `--len 5..8`", len);
+    let range = match len.find("..") {
+        Some(pos) => (
+            len[..pos].parse::<usize>().map_err(|_| invalid())?,
+            len[pos + 2..]
+                .trim_start_matches('=')
+                .parse::<usize>()
+                .map_err(|_| invalid())?,
+        ),
+        None => {
+            let len = len.parse::<usize>().map_err(|_| invalid())?;
+            (len, len)
+        }
+    };
+    (range.0 <= range.1).as_result(range, invalid())
+}
+
+/// Options restricting the drawn questions, shared by the language commands and `contest`.
+fn filter_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("unique")
+            .long("unique")
+            .takes_value(false)
+            .required(false)
+            .help("Only poses puzzles with a unique solution"),
+        Arg::with_name("difficulty")
+            .long("difficulty")
+            .takes_value(true)
+            .possible_values(&["easy", "normal", "hard"])
+            .required(false)
+            .help("Difficulty of puzzles"),
+        Arg::with_name("len")
+            .long("len")
+            .takes_value(true)
+            .validator(|len| parse_len(&len).map(|_| ()))
+            .required(false)
+            .help("Range of the number of characters, e.g. 5..8"),
+    ]
+}
+
+fn filter_of(matches: &ArgMatches) -> Filter {
+    Filter {
+        unique: matches.is_present("unique"),
+        difficulty: matches
+            .value_of("difficulty")
+            .map(|level| level.parse::<Difficulty>().unwrap()),
+        len: matches.value_of("len").map(|len| parse_len(len).unwrap()),
+    }
+}
+
+pub fn quiz<I: IntoIterator<Item = String>>(command: &str, args: I) -> clap::Result<Filter> {
+    App::new(command)
+        .version("0.0.1")
+        .setting(AppSettings::ColorNever)
+        .args(&filter_args())
+        .get_matches_from_safe(std::iter::once(command.to_string()).chain(args))
+        .map(|matches| filter_of(&matches))
+}
+
+pub fn contest<I: IntoIterator<Item = String>>(
+    args: I,
+) -> clap::Result<(u32, Vec<String>, Filter)> {
     App::new("contest")
         .version("0.0.1")
         .setting(AppSettings::ColorNever)
@@ -48,36 +111,26 @@ pub(crate) fn contest(
         )
         .arg(
             Arg::with_name("languages")
-                .required(true)
                 .use_delimiter(true)
                 .validator(language_validator)
                 .takes_value(true)
-                .default_value(facade::QUIZ_COMMANDS.to_vec().join(",").as_str())
                 .min_values(1)
-                .help("List of contest languages"),
-        )
-        .get_matches_from_safe(
-            std::iter::once("contest".to_string())
-                .chain(args.iter::<String>().filter_map(Result::ok)),
+                .help("List of contest languages (those allowed in the channel if omitted)"),
         )
+        .args(&filter_args())
+        .get_matches_from_safe(std::iter::once("contest".to_string()).chain(args))
         .map(|matches| {
             let num = matches.value_of("number").unwrap().parse::<u32>().unwrap();
             let languages = matches
                 .values_of("languages")
-                .unwrap()
-                .map(str::to_string)
-                .collect::<Vec<_>>();
-            (num, languages)
+                .map_or_else(Vec::new, |languages| {
+                    languages.map(str::to_string).collect::<Vec<_>>()
+                });
+            (num, languages, filter_of(&matches))
         })
 }
 
-#[derive(Debug)]
-pub enum Hint {
-    First(usize),
-    Random(usize),
-}
-
-pub(crate) fn hint(args: &mut serenity::framework::standard::Args) -> clap::Result<Hint> {
+pub fn hint<I: IntoIterator<Item = String>>(args: I) -> clap::Result<Hint> {
     App::new("hint")
         .version("0.0.1")
         .setting(AppSettings::ColorNever)
@@ -95,9 +148,7 @@ pub(crate) fn hint(args: &mut serenity::framework::standard::Args) -> clap::Resu
                 .help("Flag for random select hint")
                 .required(false),
         )
-        .get_matches_from_safe(
-            std::iter::once("hint".to_string()).chain(args.iter::<String>().filter_map(Result::ok)),
-        )
+        .get_matches_from_safe(std::iter::once("hint".to_string()).chain(args))
         .map(|matches| {
             let num = matches
                 .value_of("number")
@@ -112,9 +163,94 @@ pub(crate) fn hint(args: &mut serenity::framework::standard::Args) -> clap::Resu
         })
 }
 
-pub(crate) fn prefix(
-    args: &mut serenity::framework::standard::Args,
-) -> clap::Result<Option<String>> {
+pub fn history<I: IntoIterator<Item = String>>(args: I) -> clap::Result<Option<usize>> {
+    App::new("history")
+        .version("0.0.1")
+        .setting(AppSettings::ColorNever)
+        .arg(
+            Arg::with_name("id")
+                .required(false)
+                .validator(parse_validator::<usize>)
+                .help("Id of the contest to show"),
+        )
+        .get_matches_from_safe(std::iter::once("history".to_string()).chain(args))
+        .map(|matches| {
+            matches
+                .value_of("id")
+                .map(|id| id.parse::<usize>().unwrap())
+        })
+}
+
+pub fn rating<I: IntoIterator<Item = String>>(args: I) -> clap::Result<Option<String>> {
+    App::new("rating")
+        .version("0.0.1")
+        .setting(AppSettings::ColorNever)
+        .arg(
+            Arg::with_name("user")
+                .required(false)
+                .help("Name of the user to show"),
+        )
+        .get_matches_from_safe(std::iter::once("rating".to_string()).chain(args))
+        .map(|matches| matches.value_of("user").map(str::to_string))
+}
+
+pub fn reload<I: IntoIterator<Item = String>>(args: I) -> clap::Result<Option<String>> {
+    App::new("reload")
+        .version("0.0.1")
+        .setting(AppSettings::ColorNever)
+        .arg(
+            Arg::with_name("language")
+                .required(false)
+                .validator(language_validator)
+                .help("Language of the dictionary to reload (all if omitted)"),
+        )
+        .get_matches_from_safe(std::iter::once("reload".to_string()).chain(args))
+        .map(|matches| matches.value_of("language").map(str::to_string))
+}
+
+#[derive(Debug)]
+pub enum Timeout {
+    Show,
+    Set(u64, Option<u64>),
+    Off,
+}
+
+pub fn timeout<I: IntoIterator<Item = String>>(args: I) -> clap::Result<Timeout> {
+    App::new("timeout")
+        .version("0.0.1")
+        .setting(AppSettings::ColorNever)
+        .subcommand(
+            SubCommand::with_name("set")
+                .about("set time limit of questions")
+                .setting(AppSettings::ColorNever)
+                .arg(
+                    Arg::with_name("seconds")
+                        .required(true)
+                        .validator(range_validator(0, 3600))
+                        .help("Seconds until the answer is revealed"),
+                )
+                .arg(
+                    Arg::with_name("warn")
+                        .long("warn")
+                        .takes_value(true)
+                        .validator(parse_validator::<u64>)
+                        .help("Reminds when this many seconds remain"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("off").about("disable time limit of questions"))
+        .get_matches_from_safe(std::iter::once("timeout".to_string()).chain(args))
+        .map(|matches| match matches.subcommand() {
+            ("set", Some(arg)) => Timeout::Set(
+                arg.value_of("seconds").unwrap().parse::<u64>().unwrap(),
+                arg.value_of("warn")
+                    .map(|warn| warn.parse::<u64>().unwrap()),
+            ),
+            ("off", _) => Timeout::Off,
+            _ => Timeout::Show,
+        })
+}
+
+pub fn prefix<I: IntoIterator<Item = String>>(args: I) -> clap::Result<Option<String>> {
     App::new("prefix")
         .version("0.0.1")
         .setting(AppSettings::ColorNever)
@@ -129,10 +265,7 @@ pub(crate) fn prefix(
                         .help("Sets new prefix if any"),
                 ),
         )
-        .get_matches_from_safe(
-            std::iter::once("prefix".to_string())
-                .chain(args.iter::<String>().filter_map(Result::ok)),
-        )
+        .get_matches_from_safe(std::iter::once("prefix".to_string()).chain(args))
         .map(|matches| {
             matches
                 .subcommand_matches("set")
@@ -140,3 +273,207 @@ pub(crate) fn prefix(
                 .flatten()
         })
 }
+
+/// Names of the settings handled by `~config`.
+pub const CONFIG_KEYS: &[&str] = &[
+    "enabled",
+    "prefix",
+    "languages",
+    "timeout",
+    "moderators",
+    "x_system",
+];
+
+/// A value given to `~config set`.
+#[derive(Debug)]
+pub enum Setting {
+    Enabled(bool),
+    Prefix(String),
+    Languages(Vec<String>),
+    /// Seconds (`0` for no limit) and when to remind.
+    Timeout(u64, Option<u64>),
+    /// Role ids.
+    Moderators(Vec<u64>),
+    XSystem(bool),
+}
+
+/// `guild` is set by `--guild`, which applies the change to the whole guild.
+#[derive(Debug)]
+pub enum Config {
+    Show,
+    Set { setting: Setting, guild: bool },
+    Unset { key: String, guild: bool },
+}
+
+fn guild_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("guild")
+        .long("guild")
+        .takes_value(false)
+        .help("Changes the default of the guild instead of the channel")
+}
+
+/// Parses a role id or a role mention `<@&id>`.
+fn parse_role(role: &str) -> Result<u64, String> {
+    role.trim_start_matches("<@&")
+        .trim_end_matches('>')
+        .parse::<u64>()
+        .map_err(|_| format!("`{}` is not a role.", role))
+}
+
+fn timeout_validator(seconds: String) -> Result<(), String> {
+    if seconds == "off" {
+        Ok(())
+    } else {
+        range_validator(0, 3600)(seconds)
+    }
+}
+
+pub fn config<I: IntoIterator<Item = String>>(args: I) -> clap::Result<Config> {
+    App::new("config")
+        .version("0.0.1")
+        .setting(AppSettings::ColorNever)
+        .subcommand(
+            SubCommand::with_name("set")
+                .about("set a setting of the channel or the guild")
+                .setting(AppSettings::ColorNever)
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("enabled")
+                        .arg(
+                            Arg::with_name("value")
+                                .required(true)
+                                .possible_values(&["on", "off"]),
+                        )
+                        .arg(guild_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("prefix")
+                        .arg(
+                            Arg::with_name("value")
+                                .required(true)
+                                .validator(prefix_validator),
+                        )
+                        .arg(guild_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("languages")
+                        .arg(
+                            Arg::with_name("value")
+                                .required(true)
+                                .use_delimiter(true)
+                                .min_values(1)
+                                .validator(language_validator),
+                        )
+                        .arg(guild_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("timeout")
+                        .arg(
+                            Arg::with_name("value")
+                                .required(true)
+                                .validator(timeout_validator)
+                                .help("Seconds until the answer is revealed, or `off`"),
+                        )
+                        .arg(
+                            Arg::with_name("warn")
+                                .long("warn")
+                                .takes_value(true)
+                                .validator(parse_validator::<u64>)
+                                .help("Reminds when this many seconds remain"),
+                        )
+                        .arg(guild_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("moderators")
+                        .arg(
+                            Arg::with_name("value")
+                                .required(true)
+                                .use_delimiter(true)
+                                .min_values(1)
+                                .validator(|role| parse_role(&role).map(|_| ()))
+                                .help("Role ids or mentions"),
+                        )
+                        .arg(guild_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("x_system")
+                        .arg(
+                            Arg::with_name("value")
+                                .required(true)
+                                .possible_values(&["on", "off"])
+                                .help("Shows ĉ, ĝ, ... as cx, gx, ..."),
+                        )
+                        .arg(guild_arg()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unset")
+                .about("inherit a setting from the guild (or the default)")
+                .setting(AppSettings::ColorNever)
+                .arg(
+                    Arg::with_name("key")
+                        .required(true)
+                        .possible_values(CONFIG_KEYS),
+                )
+                .arg(guild_arg()),
+        )
+        .get_matches_from_safe(std::iter::once("config".to_string()).chain(args))
+        .map(|matches| match matches.subcommand() {
+            ("set", Some(set)) => {
+                let (key, arg) = set.subcommand();
+                let arg = arg.unwrap();
+                let value = arg.value_of("value").unwrap();
+                let setting = match key {
+                    "enabled" => Setting::Enabled(value == "on"),
+                    "prefix" => Setting::Prefix(value.to_string()),
+                    "x_system" => Setting::XSystem(value == "on"),
+                    "languages" => Setting::Languages(
+                        arg.values_of("value")
+                            .unwrap()
+                            .map(str::to_string)
+                            .collect(),
+                    ),
+                    "moderators" => Setting::Moderators(
+                        arg.values_of("value")
+                            .unwrap()
+                            .map(|role| parse_role(role).unwrap())
+                            .collect(),
+                    ),
+                    _ => Setting::Timeout(
+                        value.parse::<u64>().unwrap_or(0),
+                        arg.value_of("warn")
+                            .map(|warn| warn.parse::<u64>().unwrap()),
+                    ),
+                };
+                Config::Set {
+                    setting,
+                    guild: arg.is_present("guild"),
+                }
+            }
+            ("unset", Some(arg)) => Config::Unset {
+                key: arg.value_of("key").unwrap().to_string(),
+                guild: arg.is_present("guild"),
+            },
+            _ => Config::Show,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn len_ranges() {
+        assert_eq!(parse_len("5..8"), Ok((5, 8)));
+        assert_eq!(parse_len("5..=8"), Ok((5, 8)));
+        assert_eq!(parse_len("6"), Ok((6, 6)));
+        assert_eq!(parse_len("6..6"), Ok((6, 6)));
+    }
+
+    #[test]
+    fn invalid_len_ranges() {
+        for len in &["8..5", "..5", "5..", "a..5", "five", "-1", ""] {
+            assert!(parse_len(len).is_err(), "{}", len);
+        }
+    }
+}
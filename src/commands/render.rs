@@ -0,0 +1,156 @@
+use crate::bot::{self, Event, Hint, HintError};
+use crate::error::BotError;
+use crate::settings::permission::Level;
+use itertools::Itertools;
+
+const MAX_OTHER_SOLUTIONS: usize = 10;
+
+fn other_solutions(others: &[String]) -> String {
+    if others.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n他の解 ({}個): {}{}",
+            others.len(),
+            others.iter().take(MAX_OTHER_SOLUTIONS).join(", "),
+            if others.len() > MAX_OTHER_SOLUTIONS {
+                ", ..."
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// Renders a game event as a chat message.
+pub fn render(event: &Event) -> String {
+    match event {
+        Event::QuestionPosed {
+            lang,
+            sorted,
+            progress: None,
+            ..
+        } => format!(
+            "ソートなぞなぞ ソート前の {as_str} な〜んだ？\n`{prob}`",
+            as_str = lang.as_symbol(),
+            prob = sorted
+        ),
+        Event::QuestionPosed {
+            lang,
+            sorted,
+            progress: Some((current, number)),
+            ..
+        } => format!(
+            "問 {current} ({current}/{number})\nソートなぞなぞ ソート前の {symbol} な〜んだ？\n`{prob}`",
+            number = number,
+            current = current,
+            prob = sorted,
+            symbol = lang.as_symbol(),
+        ),
+        Event::ContestStarted { number } => format!("{}問のコンテストを始めます。", number),
+        Event::Solved {
+            player,
+            answer,
+            elapsed,
+            others,
+        } => format!(
+            "{} さん、正解です！\n正解は\"{}\"でした！ [{:.3} sec]{}",
+            player,
+            answer,
+            elapsed,
+            other_solutions(others),
+        ),
+        Event::AlternativeSolved {
+            player,
+            answer,
+            in_questions: true,
+        } => format!("{} さん、{} は非想定解ですが正解です！", player, answer),
+        Event::AlternativeSolved {
+            player,
+            answer,
+            in_questions: false,
+        } => format!(
+            "{} さん、{} は出題辞書にない非想定解ですが正解です！",
+            player, answer
+        ),
+        Event::NearMiss { player, answer } => format!(
+            "{} さん、惜しい！ {} はアクセント記号が違います。",
+            player, answer
+        ),
+        Event::Revealed { answer, others } => format!(
+            "正解は \"{}\" でした...{}",
+            answer,
+            other_solutions(others)
+        ),
+        Event::ContestFinished {
+            number, standings, ..
+        } => format!(
+            "{num}問連続のコンテストが終了しました。\n{result}",
+            num = number,
+            result = bot::aggregates(standings)
+        ),
+        Event::ContestAborted => "コンテストを中止します。".to_string(),
+        Event::TimedOut => "時間切れです！".to_string(),
+        Event::Remaining { seconds } => format!("残り{}秒", seconds),
+        Event::NoCandidate { lang } => format!(
+            "条件に合う {} が辞書にありません。",
+            lang.as_symbol()
+        ),
+        Event::NotPosed => "現在問題は出ていません。".to_string(),
+        Event::NotContesting => "現在コンテストは開催されていません。".to_string(),
+        Event::HintGiven {
+            hint: Hint::First(num),
+            text,
+        } => format!("答えの先頭 {len} 文字は... => `{hint}` ", len = num, hint = text),
+        Event::HintGiven {
+            hint: Hint::Random(num),
+            text,
+        } => format!("ランダムヒント {len} 文字... => `{hint}` ", len = num, hint = text),
+        Event::HintRejected(HintError::NoQuestion) => "問題が出てないですよ？".to_string(),
+        Event::HintRejected(HintError::Zero) => "ゼロ文字ヒントはだせません。".to_string(),
+        Event::HintRejected(HintError::Unique) => {
+            "答えが一意に定まるためギブアップとみなされました！".to_string()
+        }
+        Event::HintRejected(HintError::TooLong) => "ヒントが文字数を超えていますｗ".to_string(),
+    }
+}
+
+/// Renders a failed command as a chat message.
+pub fn error(err: &anyhow::Error) -> String {
+    match err.downcast_ref::<BotError>() {
+        Some(BotError::UnknownLanguage(lang)) => format!("`{}` という言語はありません。", lang),
+        Some(BotError::NoLanguage) => "言語を指定してください。".to_string(),
+        Some(BotError::MissingDictionary(path)) => {
+            format!("辞書 `{}` が見つかりません。", path.display())
+        }
+        Some(BotError::DictionarySyntaxError {
+            file, line, column, ..
+        }) => format!(
+            "辞書 `{}` の {} 行 {} 列目を読み込めません。",
+            file.display(),
+            line,
+            column
+        ),
+        Some(BotError::SettingsIo { .. }) | Some(BotError::ParseError(_)) => {
+            "設定を保存できませんでした。".to_string()
+        }
+        Some(BotError::LockPoisoned(_)) => {
+            "内部エラーが発生しました。しばらくしてからお試しください。".to_string()
+        }
+        Some(BotError::ContestState) => {
+            "コンテストの状態が壊れていたためリセットしました。もう一度お試しください。".to_string()
+        }
+        _ => format!("エラーが発生しました: {}", err),
+    }
+}
+
+/// Tells who may run a command that was refused.
+pub fn permission_denied(prefix: &str, command: &str, required: Level) -> String {
+    let who = match required {
+        Level::Everyone => "全員",
+        Level::Moderator => "モデレーターのロールを持つメンバーか管理者",
+        Level::Administrator => "サーバーの管理者",
+        Level::Owner => "botのオーナー",
+    };
+    format!("`{}{}` を実行できるのは{}のみです。", prefix, command, who)
+}
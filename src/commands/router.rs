@@ -0,0 +1,189 @@
+use super::super::bot;
+use super::super::chat::{Message, Transport};
+use super::super::error::{BotError, Lock};
+use super::super::language::LANGUAGES;
+use super::super::sandbox;
+use super::super::settings;
+use super::super::settings::permission::{self, Level};
+use super::{executors, facade, render};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// Routes incoming messages to the commands, the language quizzes and the answer check.
+pub struct Router {
+    owners: HashSet<u64>,
+    /// Mentioning the bot works as a prefix.
+    bot_id: u64,
+    last_used: Mutex<HashMap<(facade::Bucket, u64), DateTime<Utc>>>,
+}
+
+lazy_static! {
+    static ref KICK: Regex = Regex::new(r"^kick\(.*\);$").unwrap();
+}
+
+/// Prefix of the channel; `~` unless set for the channel or its guild.
+pub fn prefix(msg: &Message) -> Result<String, BotError> {
+    Ok(settings::SETTINGS.locked()?.prefix(msg.channel, msg.guild))
+}
+
+fn is_enabled(msg: &Message) -> Result<bool, BotError> {
+    Ok(settings::SETTINGS
+        .locked()?
+        .is_enabled(msg.channel, msg.guild))
+}
+
+impl Router {
+    pub fn new(owners: HashSet<u64>, bot_id: u64) -> Router {
+        Router {
+            owners,
+            bot_id,
+            last_used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn dispatch(&self, chat: &Transport, msg: &Message) {
+        if msg.bot {
+            return;
+        }
+        if let Some(guild) = msg.guild {
+            if let Err(err) = settings::record_guild(msg.channel, guild) {
+                println!("{:?}", err);
+            }
+        }
+        let routed = match self.command_of(msg) {
+            Ok(Some((name, args))) => self.command(chat, msg, name, args),
+            Ok(None) if sandbox::enabled() && KICK.is_match(&msg.content) => {
+                executors::kick(chat, msg);
+                Ok(())
+            }
+            Ok(None) => executors::answer_check(chat, msg).map_err(Into::into),
+            // the channel is unknown to be enabled, so the failure is only logged.
+            Err(err) => {
+                println!("{:?}", err);
+                return;
+            }
+        };
+        if let Err(err) = routed {
+            println!("{:?}", err);
+            chat.say(msg.channel, render::error(&err));
+        }
+        if let Err(err) = bot::release(msg.channel) {
+            println!("{:?}", err);
+        }
+    }
+
+    /// Splits a command into its name and arguments; direct messages need no prefix.
+    fn command_of<'a>(&self, msg: &'a Message) -> Result<Option<(&'a str, Vec<String>)>, BotError> {
+        let content = msg.content.trim_start();
+        let mentions = [
+            format!("<@{}>", self.bot_id),
+            format!("<@!{}>", self.bot_id),
+        ];
+        let prefix = prefix(msg)?;
+        let rest = if content.starts_with(prefix.as_str()) {
+            &content[prefix.len()..]
+        } else if let Some(mention) = mentions.iter().find(|m| content.starts_with(m.as_str())) {
+            content[mention.len()..].trim_start()
+        } else if msg.guild.is_none() {
+            let name = content.split_whitespace().next().unwrap_or("");
+            if name != "help"
+                && facade::find(name).is_none()
+                && LANGUAGES.by_command(name).is_none()
+            {
+                return Ok(None);
+            }
+            content
+        } else {
+            return Ok(None);
+        };
+        let mut words = rest.split_whitespace();
+        Ok(words
+            .next()
+            .map(|name| (name, words.map(str::to_string).collect())))
+    }
+
+    fn command(
+        &self,
+        chat: &Transport,
+        msg: &Message,
+        name: &str,
+        args: Vec<String>,
+    ) -> facade::CommandResult {
+        if name == "help" {
+            if is_enabled(msg)? {
+                chat.say(msg.channel, facade::help(&prefix(msg)?));
+            }
+            return Ok(());
+        }
+        match facade::find(name) {
+            Some(command) => {
+                let level = match self.before(chat, msg, command)? {
+                    Some(level) => level,
+                    None => return Ok(()),
+                };
+                if self.rate_limited(command.bucket, msg)? {
+                    return Ok(());
+                }
+                (command.run)(chat, msg, level, args)
+            }
+            // language commands are registered in the manifest, not in the command table.
+            None => {
+                if_chain! {
+                    if let Some(lang) = LANGUAGES.by_command(name);
+                    if is_enabled(msg)?;
+                    if !self.rate_limited(facade::Bucket::Basic, msg)?;
+                    if executors::allowed(chat, msg, lang)?;
+                    if executors::standing_by(chat, msg)?;
+                    then {
+                        executors::quiz(chat, msg, lang, args)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn before(
+        &self,
+        chat: &Transport,
+        msg: &Message,
+        command: &facade::Command,
+    ) -> Result<Option<Level>, BotError> {
+        let (enabled, level) = {
+            let settings = settings::SETTINGS.locked()?;
+            (
+                settings.is_enabled(msg.channel, msg.guild),
+                permission::level(&settings, &self.owners, msg),
+            )
+        };
+        // settings can be changed in disabled channels, e.g. to enable a whole guild.
+        if !enabled && command.name != "enable" && command.name != "config" {
+            return Ok(None);
+        }
+        if level < command.permission {
+            chat.say(
+                msg.channel,
+                render::permission_denied(&prefix(msg)?, command.name, command.permission),
+            );
+            return Ok(None);
+        }
+        if command.name == "contest" && !executors::standing_by(chat, msg)? {
+            return Ok(None);
+        }
+        Ok(Some(level))
+    }
+
+    fn rate_limited(&self, bucket: facade::Bucket, msg: &Message) -> Result<bool, BotError> {
+        let mut last_used = self.last_used.locked()?;
+        let key = (bucket, msg.author_id);
+        Ok(match last_used.get(&key) {
+            Some(last) if msg.timestamp < *last + bucket.interval() => true,
+            _ => {
+                last_used.insert(key, msg.timestamp);
+                false
+            }
+        })
+    }
+}
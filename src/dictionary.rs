@@ -1,17 +1,138 @@
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 use rand::distributions::{Distribution, Uniform};
 use serde_derive::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::Read;
+use std::sync::{Arc, RwLock};
 
+use super::error::{BotError, ReadWriteLock};
+use super::language::{canonicalize, normalize, Normalization};
+use super::sort::Sorted;
 use std::{env, path::Path};
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug)]
 pub struct Dictionary {
-    questions: IndexSet<String>,
-    full: Option<IndexSet<String>>,
+    /// Normalized form => spelling of the word as shown to players.
+    questions: IndexMap<String, String>,
+    full: Option<IndexMap<String, String>>,
+    /// Sorted normalized form => every word of `questions` and `full` with that form, as in
+    /// `questions`.
+    index: IndexMap<String, IndexMap<String, String>>,
+    normalization: Vec<Normalization>,
+    /// Metadata of each question, in the order of `questions`.
+    info: Vec<WordInfo>,
     dist: Uniform<usize>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("unexpected difficulty '{}'.", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WordInfo {
+    /// Number of graphemes.
+    pub len: usize,
+    /// Number of valid solutions of the puzzle.
+    pub solutions: usize,
+    pub score: f32,
+    pub difficulty: Difficulty,
+}
+
+/// Restricts which questions may be drawn.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    /// Only draw words whose sorted form has exactly one solution.
+    pub unique: bool,
+    pub difficulty: Option<Difficulty>,
+    /// Inclusive range of the number of graphemes.
+    pub len: Option<(usize, usize)>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        *self == Filter::default()
+    }
+
+    pub fn accepts(&self, info: &WordInfo) -> bool {
+        (!self.unique || info.solutions == 1)
+            && self
+                .difficulty
+                .map_or(true, |level| level == info.difficulty)
+            && self
+                .len
+                .map_or(true, |(low, up)| low <= info.len && info.len <= up)
+    }
+}
+
+/// Scores every question and splits them into three levels of the same size.
+///
+/// Longer words and words made of rare graphemes are harder, while repeated graphemes and
+/// alternative solutions make a puzzle easier.
+fn word_info(questions: &IndexMap<String, String>, solutions: &[usize]) -> Vec<WordInfo> {
+    let mut frequency = IndexMap::<&str, usize>::new();
+    let mut total = 0usize;
+    for word in questions.keys() {
+        for grapheme in UnicodeSegmentation::graphemes(word.as_str(), true) {
+            *frequency.entry(grapheme).or_insert(0) += 1;
+            total += 1;
+        }
+    }
+    let scores = questions
+        .keys()
+        .zip(solutions)
+        .map(|(word, solutions)| {
+            let graphemes = UnicodeSegmentation::graphemes(word.as_str(), true).collect::<Vec<_>>();
+            let len = graphemes.len();
+            let distinct = graphemes.iter().collect::<IndexSet<_>>().len();
+            let rarity = graphemes
+                .iter()
+                .map(|grapheme| (total as f32 / frequency[grapheme] as f32).log2())
+                .sum::<f32>()
+                / len.max(1) as f32;
+            let score = len as f32 - 0.5 * (len - distinct) as f32 + 0.5 * rarity
+                - (*solutions as f32 - 1.0);
+            (len, *solutions, score)
+        })
+        .collect::<Vec<_>>();
+    let mut sorted = scores
+        .iter()
+        .map(|(_, _, score)| *score)
+        .collect::<Vec<_>>();
+    sorted.sort_by(|lhs, rhs| lhs.partial_cmp(rhs).unwrap());
+    let (easy, normal) = (sorted[sorted.len() / 3], sorted[sorted.len() * 2 / 3]);
+    scores
+        .into_iter()
+        .map(|(len, solutions, score)| WordInfo {
+            len,
+            solutions,
+            score,
+            difficulty: if score < easy {
+                Difficulty::Easy
+            } else if score < normal {
+                Difficulty::Normal
+            } else {
+                Difficulty::Hard
+            },
+        })
+        .collect()
+}
 #[derive(Debug, Serialize, Deserialize)]
 struct RawDictionary {
     questions: Vec<String>,
@@ -20,7 +141,24 @@ struct RawDictionary {
 
 impl Dictionary {
     pub fn get<Rng: rand::Rng>(&self, engine: &mut Rng) -> &String {
-        self.questions.get_index(self.dist.sample(engine)).unwrap()
+        self.questions
+            .get_index(self.dist.sample(engine))
+            .unwrap()
+            .1
+    }
+
+    /// Draws a question satisfying `filter`, or `None` if there is no such question.
+    pub fn pick<Rng: rand::Rng>(&self, filter: &Filter, engine: &mut Rng) -> Option<&String> {
+        use rand::seq::IteratorRandom;
+        if filter.is_empty() {
+            return Some(self.get(engine));
+        }
+        self.questions
+            .values()
+            .zip(self.info.iter())
+            .filter(|(_, info)| filter.accepts(info))
+            .map(|(word, _)| word)
+            .choose(engine)
     }
 
     pub fn len(&self) -> usize {
@@ -31,82 +169,207 @@ impl Dictionary {
         self.full.as_ref().map(|dic| dic.len())
     }
 
+    /// Form of `word` compared with the dictionary.
+    pub fn normalize(&self, word: &str) -> String {
+        normalize(&self.normalization, word)
+    }
+
     pub fn contains(&self, word: &str) -> bool {
-        self.questions.contains(word)
+        self.questions.contains_key(&self.normalize(word))
     }
 
     pub fn contains_ex(&self, word: &str) -> bool {
         self.full
             .as_ref()
-            .map(|x| x.contains(word))
+            .map(|x| x.contains_key(&self.normalize(word)))
             .unwrap_or(false)
     }
 
-    pub fn from_toml<S: AsRef<Path>>(file: S) -> std::io::Result<Dictionary> {
-        let mut f = File::open(Path::new(&env::var("DIC_DIR").unwrap()).join(file))?;
+    /// The dictionary spelling of `word`, if it is in the dictionary.
+    pub fn spelling(&self, word: &str) -> Option<&String> {
+        let word = self.normalize(word);
+        self.questions
+            .get(&word)
+            .or_else(|| self.full.as_ref().and_then(|full| full.get(&word)))
+    }
+
+    /// All words that are anagrams of `word`, including `word` itself if it is in the dictionary,
+    /// keyed by their normalized forms.
+    pub fn anagrams(&self, word: &str) -> Option<&IndexMap<String, String>> {
+        self.index.get(&self.normalize(word).sorted())
+    }
+
+    /// Number of valid solutions of the puzzle made from `word`.
+    pub fn solutions(&self, word: &str) -> usize {
+        self.anagrams(word).map_or(0, IndexMap::len)
+    }
+
+    pub fn from_toml<S: AsRef<Path>>(
+        file: S,
+        normalization: &[Normalization],
+    ) -> Result<Dictionary, BotError> {
+        let path = Path::new(&env::var("DIC_DIR")?).join(file);
+        let mut f = File::open(&path).map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => BotError::MissingDictionary(path.clone()),
+            _ => BotError::IoError(err),
+        })?;
         let mut buffer = String::new();
         // config file open
         // read config.toml
-        let _ = f.read_to_string(&mut buffer).unwrap();
+        let _ = f.read_to_string(&mut buffer)?;
         // parse toml
-        let raw: RawDictionary =
-            toml::from_slice(buffer.as_bytes()).expect("could not parse dictionary!");
-        let mut questions = IndexSet::new();
-        for word in raw.questions {
-            questions.insert(word.to_lowercase());
-        }
-        let full = if let Some(full) = raw.full {
-            let mut full_dic = IndexSet::new();
-            for word in full {
-                full_dic.insert(word.to_lowercase());
+        let raw: RawDictionary = toml::from_slice(buffer.as_bytes())
+            .map_err(|err| BotError::dictionary_syntax(path.clone(), err))?;
+        // words only differing in folded forms are the same word; the first spelling is kept.
+        let mut decomposed = Vec::new();
+        let mut spellings = |words: Vec<String>| {
+            let mut spellings = IndexMap::new();
+            for word in words {
+                if !unicode_normalization::is_nfc(&word) {
+                    decomposed.push(word.clone());
+                }
+                spellings
+                    .entry(normalize(normalization, &word))
+                    .or_insert_with(|| canonicalize(normalization, &word));
             }
-            Some(full_dic)
-        } else {
-            None
+            spellings
         };
+        let questions = spellings(raw.questions);
+        if questions.is_empty() {
+            return Err(BotError::EmptyDictionary);
+        }
+        let full = raw.full.map(spellings);
+        if !decomposed.is_empty() {
+            println!(
+                "warning: {} has {} words not in NFC, which are composed: {}",
+                path.display(),
+                decomposed.len(),
+                decomposed.join(", ")
+            );
+        }
+        let mut index = IndexMap::<String, IndexMap<String, String>>::new();
+        for (word, spelling) in questions.iter().chain(full.iter().flatten()) {
+            index
+                .entry(word.sorted())
+                .or_insert_with(IndexMap::new)
+                .entry(word.clone())
+                .or_insert_with(|| spelling.clone());
+        }
+        let solutions = questions
+            .keys()
+            .map(|word| index[&word.sorted()].len())
+            .collect::<Vec<_>>();
+        let info = word_info(&questions, &solutions);
         let dist = Uniform::new(0, questions.len());
         Ok(Dictionary {
             questions,
             full,
+            index,
+            normalization: normalization.to_vec(),
+            info,
             dist,
         })
     }
 }
 
-lazy_static! {
-    pub static ref ENGLISH: Dictionary = {
-        let dictionary: Dictionary = Dictionary::from_toml("english.toml").unwrap();
-        println!("ENGLISH is loaded: len = {}", dictionary.questions.len());
-        dictionary
-    };
-    pub static ref JAPANESE: Dictionary = {
-        let dictionary: Dictionary = Dictionary::from_toml("japanese.toml").unwrap();
-        println!("JAPANESE is loaded: len = {}", dictionary.questions.len());
-        dictionary
-    };
-    pub static ref FRENCH: Dictionary = {
-        let dictionary: Dictionary = Dictionary::from_toml("french.toml").unwrap();
-        println!("FRENCH is loaded: len = {}", dictionary.questions.len());
-        dictionary
-    };
-    pub static ref GERMAN: Dictionary = {
-        let dictionary: Dictionary = Dictionary::from_toml("german.toml").unwrap();
-        println!("GERMAN is loaded: len = {}", dictionary.questions.len());
-        dictionary
-    };
-    pub static ref ITALIAN: Dictionary = {
-        let dictionary: Dictionary = Dictionary::from_toml("italian.toml").unwrap();
-        println!("ITALIAN is loaded: len = {}", dictionary.questions.len());
-        dictionary
-    };
-    pub static ref RUSSIAN: Dictionary = {
-        let dictionary: Dictionary = Dictionary::from_toml("russian.toml").unwrap();
-        println!("RUSSIAN is loaded: len = {}", dictionary.questions.len());
-        dictionary
-    };
-    pub static ref ESPERANTO: Dictionary = {
-        let dictionary: Dictionary = Dictionary::from_toml("esperanto.toml").unwrap();
-        println!("ESPERANTO is loaded: len = {}", dictionary.questions.len());
-        dictionary
-    };
+/// A dictionary that can be swapped while the bot is running.
+pub type DictionarySlot = RwLock<Arc<Dictionary>>;
+
+pub fn load(file: &str, normalization: &[Normalization]) -> Result<DictionarySlot, BotError> {
+    Ok(RwLock::new(Arc::new(Dictionary::from_toml(
+        file,
+        normalization,
+    )?)))
+}
+
+/// Re-parses the dictionary file and swaps it in, keeping the old one on failure.
+pub fn reload(
+    slot: &DictionarySlot,
+    file: &str,
+    normalization: &[Normalization],
+) -> Result<usize, BotError> {
+    let dictionary = Dictionary::from_toml(file, normalization)?;
+    let len = dictionary.len();
+    *slot.write_locked()? = Arc::new(dictionary);
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn questions(words: &[&str]) -> IndexMap<String, String> {
+        words
+            .iter()
+            .map(|word| (word.to_string(), word.to_string()))
+            .collect()
+    }
+
+    fn info(len: usize, solutions: usize, difficulty: Difficulty) -> WordInfo {
+        WordInfo {
+            len,
+            solutions,
+            score: 0.0,
+            difficulty,
+        }
+    }
+
+    #[test]
+    fn word_info_counts_graphemes() {
+        let words = questions(&["ab", "cde", "e\u{301}fgh"]);
+        let info = word_info(&words, &[1, 1, 1]);
+        let lens = info.iter().map(|info| info.len).collect::<Vec<_>>();
+        assert_eq!(lens, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn word_info_scores() {
+        let words = questions(&["abcd", "abcdef", "aabb", "dcba"]);
+        let info = word_info(&words, &[1, 1, 1, 3]);
+        // longer words are harder, repeated graphemes and other solutions make it easier.
+        assert!(info[1].score > info[0].score);
+        assert!(info[2].score < info[0].score);
+        assert!(info[3].score < info[0].score);
+        assert_eq!(info[3].solutions, 3);
+    }
+
+    #[test]
+    fn word_info_splits_into_thirds() {
+        let words = questions(&["ab", "abc", "abcd", "abcde", "abcdef", "abcdefg"]);
+        let info = word_info(&words, &[1; 6]);
+        let levels = info.iter().map(|info| info.difficulty).collect::<Vec<_>>();
+        assert_eq!(
+            levels,
+            vec![
+                Difficulty::Easy,
+                Difficulty::Easy,
+                Difficulty::Normal,
+                Difficulty::Normal,
+                Difficulty::Hard,
+                Difficulty::Hard,
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_filter_accepts_everything() {
+        let filter = Filter::default();
+        assert!(filter.is_empty());
+        assert!(filter.accepts(&info(1, 3, Difficulty::Hard)));
+    }
+
+    #[test]
+    fn filter_accepts() {
+        let filter = Filter {
+            unique: true,
+            difficulty: Some(Difficulty::Normal),
+            len: Some((5, 8)),
+        };
+        assert!(filter.accepts(&info(5, 1, Difficulty::Normal)));
+        assert!(filter.accepts(&info(8, 1, Difficulty::Normal)));
+        assert!(!filter.accepts(&info(4, 1, Difficulty::Normal)));
+        assert!(!filter.accepts(&info(9, 1, Difficulty::Normal)));
+        assert!(!filter.accepts(&info(6, 2, Difficulty::Normal)));
+        assert!(!filter.accepts(&info(6, 1, Difficulty::Easy)));
+    }
 }
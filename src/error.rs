@@ -1,9 +1,102 @@
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum BotError {
     #[error("I/O error")]
     IoError(#[from] std::io::Error),
-    #[error("Paese error")]
+    #[error("could not serialize settings")]
     ParseError(#[from] toml::ser::Error),
+    #[error("could not parse dictionary: {0}")]
+    DictionaryParseError(#[from] toml::de::Error),
+    #[error("could not parse {}:{line}:{column}: {message}", .file.display())]
+    DictionarySyntaxError {
+        file: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[error("DIC_DIR is not set")]
+    DictionaryDirError(#[from] std::env::VarError),
+    #[error("dictionary {} is not found", .0.display())]
+    MissingDictionary(PathBuf),
+    #[error("dictionary has no questions")]
+    EmptyDictionary,
+    #[error("could not read language manifest {}", .path.display())]
+    ManifestIo {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("could not parse language manifest {}: {source}", .path.display())]
+    ManifestParse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("unexpected language `{0}`")]
+    UnknownLanguage(String),
+    #[error("no language is given")]
+    NoLanguage,
+    #[error("could not access settings file {}", .path.display())]
+    SettingsIo {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("could not parse settings: {0}")]
+    SettingsParse(#[source] toml::de::Error),
+    #[error("unsupported settings version {0}")]
+    SettingsVersion(String),
+    #[error("settings were written in version {0}, newer than this build")]
+    SettingsTooNew(i64),
+    #[error("{0} lock is poisoned")]
+    LockPoisoned(&'static str),
+    #[error("contest results were not reset")]
+    ContestState,
+}
+
+impl BotError {
+    /// Tells a parse error of `file` with its (1-based) position.
+    pub fn dictionary_syntax(file: PathBuf, err: toml::de::Error) -> BotError {
+        match err.line_col() {
+            Some((line, column)) => BotError::DictionarySyntaxError {
+                file,
+                line: line + 1,
+                column: column + 1,
+                message: err.to_string(),
+            },
+            None => BotError::DictionaryParseError(err),
+        }
+    }
+}
+
+/// Lock acquisition that reports poisoning as [`BotError::LockPoisoned`] instead of panicking.
+pub trait Lock<T> {
+    fn locked(&self) -> Result<MutexGuard<T>, BotError>;
+}
+
+impl<T> Lock<T> for Mutex<T> {
+    fn locked(&self) -> Result<MutexGuard<T>, BotError> {
+        self.lock()
+            .map_err(|_| BotError::LockPoisoned(std::any::type_name::<T>()))
+    }
+}
+
+pub trait ReadWriteLock<T> {
+    fn read_locked(&self) -> Result<RwLockReadGuard<T>, BotError>;
+    fn write_locked(&self) -> Result<RwLockWriteGuard<T>, BotError>;
+}
+
+impl<T> ReadWriteLock<T> for RwLock<T> {
+    fn read_locked(&self) -> Result<RwLockReadGuard<T>, BotError> {
+        self.read()
+            .map_err(|_| BotError::LockPoisoned(std::any::type_name::<T>()))
+    }
+
+    fn write_locked(&self) -> Result<RwLockWriteGuard<T>, BotError> {
+        self.write()
+            .map_err(|_| BotError::LockPoisoned(std::any::type_name::<T>()))
+    }
 }
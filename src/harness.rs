@@ -0,0 +1,144 @@
+//! Drives the bot without Discord: scripted messages go through the same [`Router`] as the
+//! client and everything the bot posts is captured.
+use super::bot::{self, Status};
+use super::chat::{Chat, Message, Transport};
+use super::commands::router::Router;
+use super::BOT_ID;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::env;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// Name of the user treated as the bot owner.
+pub const OWNER: &str = "owner";
+/// Name of the user with the administrator permission of the guild.
+pub const ADMIN: &str = "admin";
+/// Guild of the messages sent with [`Harness::send`].
+pub const GUILD: u64 = 1;
+
+/// Records the posted messages instead of sending them.
+#[derive(Default)]
+pub struct MockChat {
+    sent: Mutex<Vec<(u64, String)>>,
+}
+
+impl Chat for MockChat {
+    fn say(&self, channel: u64, text: String) {
+        self.sent.lock().unwrap().push((channel, text));
+    }
+}
+
+impl MockChat {
+    /// Messages posted since the last call, with their channels.
+    pub fn take(&self) -> Vec<(u64, String)> {
+        std::mem::replace(&mut *self.sent.lock().unwrap(), Vec::new())
+    }
+}
+
+pub struct Harness {
+    pub chat: Arc<MockChat>,
+    transport: Transport,
+    router: Router,
+    /// Timestamp of the last scripted message.
+    pub clock: DateTime<Utc>,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Harness::new()
+    }
+}
+
+impl Harness {
+    /// Dictionaries are read from the repository unless `DIC_DIR` is set, and settings are
+    /// kept in a temporary directory of the process unless `SETTINGS_DIR` is set.
+    pub fn new() -> Harness {
+        if env::var_os("DIC_DIR").is_none() {
+            env::set_var(
+                "DIC_DIR",
+                concat!(env!("CARGO_MANIFEST_DIR"), "/dictionaries"),
+            );
+        }
+        if env::var_os("SETTINGS_DIR").is_none() {
+            env::set_var(
+                "SETTINGS_DIR",
+                env::temp_dir().join(format!("nazonazo-harness-{}", std::process::id())),
+            );
+        }
+        let chat = Arc::new(MockChat::default());
+        let mut owners = HashSet::new();
+        owners.insert(Harness::user_id(OWNER));
+        Harness {
+            chat: chat.clone(),
+            transport: chat,
+            router: Router::new(owners, BOT_ID),
+            clock: Utc::now(),
+        }
+    }
+
+    /// Stable id of the user named `name`.
+    pub fn user_id(name: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// A guild message from `author` without roles, three seconds after the previous one so
+    /// that rate limits never apply.
+    pub fn message(&mut self, author: &str, channel: u64, content: &str) -> Message {
+        self.clock = self.clock + Duration::seconds(3);
+        Message {
+            author: author.to_string(),
+            author_id: Harness::user_id(author),
+            bot: false,
+            channel,
+            guild: Some(GUILD),
+            roles: Vec::new(),
+            administrator: author == ADMIN,
+            content: content.to_string(),
+            timestamp: self.clock,
+        }
+    }
+
+    /// Sends a guild message and returns what the bot posted in response.
+    pub fn send(&mut self, author: &str, channel: u64, content: &str) -> Vec<String> {
+        let msg = self.message(author, channel, content);
+        self.deliver(&msg)
+    }
+
+    /// Routes the message and returns what the bot posted since the last delivery.
+    pub fn deliver(&mut self, msg: &Message) -> Vec<String> {
+        self.router.dispatch(&self.transport, msg);
+        self.chat.take().into_iter().map(|(_, text)| text).collect()
+    }
+
+    /// Answer of the question open in the channel.
+    pub fn answer(&self, channel: u64) -> Option<String> {
+        bot::find_session(channel)
+            .ok()??
+            .lock()
+            .unwrap()
+            .quiz
+            .ans()
+            .ok()
+            .cloned()
+    }
+
+    /// Replaces the answer of the question open in the channel with `word`, so that tests do
+    /// not depend on the question drawn.
+    pub fn pose(&self, channel: u64, word: &str) {
+        let session = bot::find_session(channel)
+            .unwrap()
+            .expect("no question is open");
+        let mut session = session.lock().unwrap();
+        match &mut session.quiz {
+            Status::Holding(ans, ..) | Status::Contesting(ans, ..) => *ans = word.to_string(),
+            Status::StandingBy => panic!("no question is open"),
+        }
+        if let Some(problem) = session.problems.last_mut() {
+            problem.answer = word.to_string();
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::bot::{self, ContestData, Contestant};
+use super::error::Lock;
+use super::settings;
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// A single question of a contest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProblemRecord {
+    pub lang: String,
+    pub question: String,
+    pub answer: String,
+    pub solver: Option<String>,
+    pub elapsed: Option<f32>,
+    pub gave_up: bool,
+    pub alternatives: Vec<AlternativeRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlternativeRecord {
+    /// User id of the player.
+    pub id: u64,
+    pub player: String,
+    pub answer: String,
+    pub elapsed: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    /// User id of the player; `None` for the questions given up.
+    pub id: Option<u64>,
+    pub name: String,
+    pub time: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContestRecord {
+    pub date: DateTime<Utc>,
+    pub channel: u64,
+    pub number: u32,
+    pub languages: Vec<String>,
+    pub problems: Vec<ProblemRecord>,
+    pub standings: Vec<Standing>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct History {
+    pub contests: Vec<ContestRecord>,
+}
+
+impl ProblemRecord {
+    pub fn new<S: Into<String>>(lang: bot::Lang, question: S, answer: S) -> ProblemRecord {
+        ProblemRecord {
+            lang: lang.as_code().to_string(),
+            question: question.into(),
+            answer: answer.into(),
+            solver: None,
+            elapsed: None,
+            gave_up: false,
+            alternatives: Vec::new(),
+        }
+    }
+
+    pub fn as_string(&self) -> String {
+        let result = match (&self.solver, self.elapsed) {
+            (Some(solver), Some(elapsed)) => format!("{} [{:.3} sec]", solver, elapsed),
+            _ if self.gave_up => "giveup".to_string(),
+            _ => "-".to_string(),
+        };
+        let alternatives = self
+            .alternatives
+            .iter()
+            .map(|alt| format!(", {}: {}", alt.player, alt.answer))
+            .collect::<String>();
+        format!(
+            "`{}` => {} ({}){}",
+            self.question, self.answer, result, alternatives
+        )
+    }
+}
+
+impl From<&(Contestant, ContestData)> for Standing {
+    fn from((contestant, data): &(Contestant, ContestData)) -> Self {
+        Standing {
+            id: match contestant {
+                Contestant::Player(id) => Some(*id),
+                Contestant::GaveUp => None,
+            },
+            name: data.name.clone(),
+            time: data.time.clone(),
+        }
+    }
+}
+
+impl ContestRecord {
+    pub fn standings(&self) -> Vec<(Contestant, ContestData)> {
+        self.standings
+            .iter()
+            .map(|standing| {
+                (
+                    standing.id.map_or(Contestant::GaveUp, Contestant::Player),
+                    ContestData {
+                        name: standing.name.clone(),
+                        time: standing.time.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// One line summary used in the list of recent contests.
+    pub fn summary(&self, id: usize) -> String {
+        format!(
+            "#{id} {date} {num}問 [{languages}] winner: {winner}",
+            id = id,
+            date = self.date.format("%Y-%m-%d %H:%M"),
+            num = self.number,
+            languages = self.languages.join(","),
+            winner = self
+                .standings
+                .first()
+                .map(|standing| standing.name.as_str())
+                .unwrap_or("-"),
+        )
+    }
+
+    pub fn details(&self, id: usize) -> String {
+        format!(
+            "{summary}\n{result}\n{problems}",
+            summary = self.summary(id),
+            result = bot::aggregates(&self.standings()),
+            problems = self
+                .problems
+                .iter()
+                .enumerate()
+                .map(|(index, problem)| format!("問 {}: {}\n", index + 1, problem.as_string()))
+                .collect::<String>(),
+        )
+    }
+}
+
+impl History {
+    /// Contests held in the channel, paired with their ids.
+    pub fn of_channel(&self, channel: u64) -> impl Iterator<Item = (usize, &ContestRecord)> {
+        self.contests
+            .iter()
+            .enumerate()
+            .map(|(index, record)| (index + 1, record))
+            .filter(move |(_, record)| record.channel == channel)
+    }
+}
+
+lazy_static! {
+    pub(crate) static ref HISTORY: Arc<Mutex<settings::Stored<History>>> = Arc::new(Mutex::new(
+        settings::Stored::load(settings::path("history.toml"))
+    ));
+}
+
+/// Appends a finished contest and writes the whole history back.
+pub(crate) fn save(record: ContestRecord) -> anyhow::Result<()> {
+    let mut history = HISTORY.locked()?;
+    history.contests.push(record);
+    history.save()
+}
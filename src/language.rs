@@ -0,0 +1,317 @@
+use super::dictionary::{self, Dictionary, DictionarySlot};
+use super::error::{BotError, ReadWriteLock};
+use serde_derive::Deserialize;
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+use std::{env, path::Path};
+use unicode_normalization::UnicodeNormalization;
+
+mod cyrillic;
+mod diacritics;
+mod esperanto;
+mod kana;
+mod romaji;
+
+pub use esperanto::to_x_system;
+
+/// Name of the language manifest in `DIC_DIR`.
+pub const MANIFEST: &str = "languages.toml";
+
+/// Handle of a language registered in the manifest.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Lang(usize);
+
+/// Normalization rules applied to dictionary words and answers, in the listed order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    Lowercase,
+    /// Full-width ASCII and half-width katakana into their usual width.
+    Width,
+    /// Katakana into hiragana.
+    Kana,
+    /// Latin letters with diacritics into their base letters, e.g. é => e.
+    Diacritics,
+    /// Esperanto x-system and h-system surrogates into their letters, e.g. cx, ch => ĉ.
+    Surrogates,
+    /// Russian ё into е.
+    Yo,
+    /// Latin transliterations of Russian into Cyrillic, e.g. zh, ž => ж.
+    Transliteration,
+    /// Romaji into hiragana, reading long vowels, ぢ and づ alike whichever way they are spelled.
+    Romaji,
+}
+
+impl Normalization {
+    pub fn apply(self, word: &str) -> String {
+        match self {
+            Normalization::Lowercase => word.to_lowercase(),
+            Normalization::Width => kana::fold_width(word),
+            Normalization::Kana => kana::fold_kana(word),
+            Normalization::Diacritics => diacritics::fold(word),
+            Normalization::Surrogates => esperanto::from_surrogates(word),
+            Normalization::Yo => cyrillic::fold_yo(word),
+            Normalization::Transliteration => cyrillic::from_latin(word),
+            Normalization::Romaji => romaji::fold_readings(&romaji::to_kana(word)),
+        }
+    }
+
+    /// Lenient rules read answers loosely enough to merge distinct words of the dictionary
+    /// (e.g. `flughaveno` as `fluĝaveno`), so words are never keyed by them; they only apply
+    /// when an answer is compared with a word, after the other rules.
+    pub fn lenient(self) -> bool {
+        match self {
+            Normalization::Surrogates | Normalization::Romaji => true,
+            _ => false,
+        }
+    }
+
+    /// Folding rules only apply to comparison; the dictionary keeps its own spelling.
+    pub fn folds(self) -> bool {
+        match self {
+            Normalization::Lowercase => false,
+            Normalization::Width
+            | Normalization::Kana
+            | Normalization::Diacritics
+            | Normalization::Surrogates
+            | Normalization::Yo
+            | Normalization::Transliteration
+            | Normalization::Romaji => true,
+        }
+    }
+}
+
+/// Composes `word` into NFC, then applies the rules in order but the lenient ones, giving
+/// the form the dictionary is keyed by.
+pub fn normalize(rules: &[Normalization], word: &str) -> String {
+    rules
+        .iter()
+        .filter(|rule| !rule.lenient())
+        .fold(word.nfc().collect(), |word, rule| rule.apply(&word))
+}
+
+/// Normalizes `word`, applies the lenient rules, then normalizes again so that the letters
+/// they read are folded too (e.g. `cx` => `ĉ` => `c` when accents are insensitive); an answer
+/// is judged to be a word when their readings are equal.
+pub fn read(rules: &[Normalization], word: &str) -> String {
+    let lenient = rules
+        .iter()
+        .filter(|rule| rule.lenient())
+        .fold(normalize(rules, word), |word, rule| rule.apply(&word));
+    normalize(rules, &lenient)
+}
+
+/// Composes `word` into NFC, then applies the rules that are not folding, giving the
+/// spelling shown to players.
+pub fn canonicalize(rules: &[Normalization], word: &str) -> String {
+    rules
+        .iter()
+        .filter(|rule| !rule.folds())
+        .fold(word.nfc().collect(), |word, rule| rule.apply(&word))
+}
+
+fn default_normalization() -> Vec<Normalization> {
+    vec![Normalization::Lowercase]
+}
+
+/// How answers differing from a word only in diacritics are judged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Accents {
+    /// They are wrong.
+    Strict,
+    /// They are correct; adds the `diacritics` rule to the normalization.
+    Insensitive,
+    /// They are wrong, but the player is told that they were close.
+    NearMiss,
+}
+
+impl Default for Accents {
+    fn default() -> Self {
+        Accents::Strict
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Language {
+    pub code: String,
+    pub command: String,
+    pub symbol: String,
+    pub dictionary: String,
+    #[serde(default = "default_normalization")]
+    pub normalization: Vec<Normalization>,
+    #[serde(default)]
+    pub accents: Accents,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    language: Vec<Language>,
+}
+
+#[derive(Debug)]
+pub struct Registry {
+    languages: Vec<(Language, DictionarySlot)>,
+}
+
+impl Registry {
+    pub fn from_toml<S: AsRef<Path>>(file: S) -> Result<Registry, BotError> {
+        let path = Path::new(&env::var("DIC_DIR")?).join(file);
+        let io = |source| BotError::ManifestIo {
+            path: path.clone(),
+            source,
+        };
+        let mut f = File::open(&path).map_err(io)?;
+        let mut buffer = String::new();
+        f.read_to_string(&mut buffer).map_err(io)?;
+        let manifest: Manifest =
+            toml::from_slice(buffer.as_bytes()).map_err(|source| BotError::ManifestParse {
+                path: path.clone(),
+                source,
+            })?;
+        let languages = manifest
+            .language
+            .into_iter()
+            .map(|mut language| {
+                if language.accents == Accents::Insensitive
+                    && !language.normalization.contains(&Normalization::Diacritics)
+                {
+                    language.normalization.push(Normalization::Diacritics);
+                }
+                let slot = dictionary::load(&language.dictionary, &language.normalization)?;
+                println!(
+                    "{} is loaded: len = {}",
+                    language.code,
+                    slot.read_locked()?.len()
+                );
+                Ok((language, slot))
+            })
+            .collect::<Result<Vec<_>, BotError>>()?;
+        Ok(Registry { languages })
+    }
+
+    pub fn get(&self, lang: Lang) -> &Language {
+        &self.languages[lang.0].0
+    }
+
+    pub fn slot(&self, lang: Lang) -> &DictionarySlot {
+        &self.languages[lang.0].1
+    }
+
+    pub fn langs(&self) -> impl Iterator<Item = Lang> {
+        (0..self.languages.len()).map(Lang)
+    }
+
+    /// Looks up a language by its code, e.g. `en`.
+    pub fn find(&self, code: &str) -> Option<Lang> {
+        self.languages
+            .iter()
+            .position(|(language, _)| language.code == code)
+            .map(Lang)
+    }
+
+    /// Looks up a language by its quiz command name.
+    pub fn by_command(&self, command: &str) -> Option<Lang> {
+        self.languages
+            .iter()
+            .position(|(language, _)| language.command == command)
+            .map(Lang)
+    }
+
+    pub fn codes(&self) -> Vec<String> {
+        self.languages
+            .iter()
+            .map(|(language, _)| language.code.clone())
+            .collect()
+    }
+}
+
+lazy_static! {
+    pub static ref LANGUAGES: Registry =
+        Registry::from_toml(MANIFEST).expect("could not load language manifest!");
+}
+
+impl Default for Lang {
+    fn default() -> Self {
+        Lang(0)
+    }
+}
+
+impl Lang {
+    pub fn as_code(self) -> &'static str {
+        &LANGUAGES.get(self).code
+    }
+
+    pub fn as_command(self) -> &'static str {
+        &LANGUAGES.get(self).command
+    }
+
+    pub fn as_symbol(self) -> String {
+        LANGUAGES.get(self).symbol.clone()
+    }
+
+    pub fn normalize(self, word: &str) -> String {
+        normalize(&LANGUAGES.get(self).normalization, word)
+    }
+
+    pub fn read(self, word: &str) -> String {
+        read(&LANGUAGES.get(self).normalization, word)
+    }
+
+    pub fn accents(self) -> Accents {
+        LANGUAGES.get(self).accents
+    }
+
+    pub fn dictionary(self) -> Result<Arc<Dictionary>, BotError> {
+        Ok(LANGUAGES.slot(self).read_locked()?.clone())
+    }
+
+    /// Reloads the dictionary from `DIC_DIR`, returning the new number of questions.
+    pub fn reload(self) -> Result<usize, BotError> {
+        let language = LANGUAGES.get(self);
+        dictionary::reload(
+            LANGUAGES.slot(self),
+            &language.dictionary,
+            &language.normalization,
+        )
+    }
+}
+
+impl std::str::FromStr for Lang {
+    type Err = BotError;
+
+    /// Looks up a language by its code.
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        LANGUAGES
+            .find(code)
+            .ok_or_else(|| BotError::UnknownLanguage(code.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surrogates_only_apply_to_readings() {
+        let rules = [Normalization::Lowercase, Normalization::Surrogates];
+        assert_eq!(normalize(&rules, "Flughaveno"), "flughaveno");
+        assert_eq!(normalize(&rules, "cxambro"), "cxambro");
+        assert_eq!(read(&rules, "cxambro"), read(&rules, "ĉambro"));
+        assert_eq!(read(&rules, "chambro"), read(&rules, "ĉambro"));
+    }
+
+    #[test]
+    fn surrogates_are_folded_when_accents_are_insensitive() {
+        let rules = [
+            Normalization::Lowercase,
+            Normalization::Surrogates,
+            Normalization::Diacritics,
+        ];
+        assert_eq!(read(&rules, "ĉambro"), "cambro");
+        assert_eq!(read(&rules, "cxambro"), "cambro");
+        assert_eq!(read(&rules, "Chambro"), "cambro");
+        assert_eq!(read(&rules, "cambro"), "cambro");
+    }
+}
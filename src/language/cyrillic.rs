@@ -0,0 +1,102 @@
+//! Folding of Russian answers: ё into е, and Latin transliterations into Cyrillic.
+
+/// Latin spellings of the Cyrillic letters, after GOST 7.79 system B (`shh`, `yu`, ...)
+/// and ISO 9 (`š`, `û`, ...); longer spellings come first so that they win.
+const LATIN: &[(&str, char)] = &[
+    ("shh", 'щ'),
+    ("zh", 'ж'),
+    ("cz", 'ц'),
+    ("ch", 'ч'),
+    ("sh", 'ш'),
+    ("yo", 'ё'),
+    ("yu", 'ю'),
+    ("ya", 'я'),
+    ("y'", 'ы'),
+    ("e'", 'э'),
+    ("''", 'ъ'),
+    ("``", 'ъ'),
+    ("a", 'а'),
+    ("b", 'б'),
+    ("v", 'в'),
+    ("g", 'г'),
+    ("d", 'д'),
+    ("e", 'е'),
+    ("ë", 'ё'),
+    ("ž", 'ж'),
+    ("z", 'з'),
+    ("i", 'и'),
+    ("j", 'й'),
+    ("k", 'к'),
+    ("l", 'л'),
+    ("m", 'м'),
+    ("n", 'н'),
+    ("o", 'о'),
+    ("p", 'п'),
+    ("r", 'р'),
+    ("s", 'с'),
+    ("t", 'т'),
+    ("u", 'у'),
+    ("f", 'ф'),
+    ("x", 'х'),
+    ("h", 'х'),
+    ("c", 'ц'),
+    ("č", 'ч'),
+    ("š", 'ш'),
+    ("ŝ", 'щ'),
+    ("ʺ", 'ъ'),
+    ("y", 'ы'),
+    ("'", 'ь'),
+    ("`", 'ь'),
+    ("ʹ", 'ь'),
+    ("è", 'э'),
+    ("û", 'ю'),
+    ("â", 'я'),
+];
+
+/// Reads ё as е.
+pub fn fold_yo(word: &str) -> String {
+    word.chars()
+        .map(|c| match c {
+            'ё' => 'е',
+            'Ё' => 'Е',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Turns a lowercase Latin transliteration into Cyrillic; other letters are kept.
+pub fn from_latin(word: &str) -> String {
+    let mut cyrillic = String::with_capacity(word.len() * 2);
+    let mut rest = word;
+    while let Some(c) = rest.chars().next() {
+        match LATIN.iter().find(|(latin, _)| rest.starts_with(latin)) {
+            Some((latin, letter)) => {
+                cyrillic.push(*letter);
+                rest = &rest[latin.len()..];
+            }
+            None => {
+                cyrillic.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    cyrillic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gost_spellings() {
+        assert_eq!(from_latin("zhenshhina"), "женщина");
+        assert_eq!(from_latin("vesnushchatyj"), "веснушчатый");
+        assert_eq!(from_latin("y'"), "ы");
+    }
+
+    #[test]
+    fn iso_spellings() {
+        assert_eq!(from_latin("ženŝina"), "женщина");
+        assert_eq!(from_latin("čaj"), "чай");
+    }
+}
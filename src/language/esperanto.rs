@@ -0,0 +1,64 @@
+//! Surrogates of the circumflexed Esperanto letters: the x-system (`cx` for `ĉ`) and the
+//! h-system (`ch` for `ĉ`).
+
+/// Letters with their x-system surrogates.
+const LETTERS: &[(char, char)] = &[
+    ('ĉ', 'c'),
+    ('ĝ', 'g'),
+    ('ĥ', 'h'),
+    ('ĵ', 'j'),
+    ('ŝ', 's'),
+    ('ŭ', 'u'),
+    ('Ĉ', 'C'),
+    ('Ĝ', 'G'),
+    ('Ĥ', 'H'),
+    ('Ĵ', 'J'),
+    ('Ŝ', 'S'),
+    ('Ŭ', 'U'),
+];
+
+fn letter(base: char) -> Option<char> {
+    LETTERS
+        .iter()
+        .find(|(_, surrogate)| *surrogate == base)
+        .map(|(letter, _)| *letter)
+}
+
+/// Turns the x-system and h-system surrogates into the circumflexed letters.
+///
+/// The h-system has no surrogate for `ŭ`, which it writes as a plain `u`.
+pub fn from_surrogates(word: &str) -> String {
+    let mut folded = String::with_capacity(word.len());
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (letter(c), chars.peek()) {
+            (Some(letter), Some('x')) | (Some(letter), Some('X')) => {
+                chars.next();
+                folded.push(letter);
+            }
+            (Some(letter), Some('h')) | (Some(letter), Some('H'))
+                if !c.eq_ignore_ascii_case(&'u') =>
+            {
+                chars.next();
+                folded.push(letter);
+            }
+            _ => folded.push(c),
+        }
+    }
+    folded
+}
+
+/// Writes the circumflexed letters in the x-system, always with a lowercase `x`.
+pub fn to_x_system(text: &str) -> String {
+    let mut written = String::with_capacity(text.len());
+    for c in text.chars() {
+        match LETTERS.iter().find(|(letter, _)| *letter == c) {
+            Some((_, surrogate)) => {
+                written.push(*surrogate);
+                written.push('x');
+            }
+            None => written.push(c),
+        }
+    }
+    written
+}
@@ -0,0 +1,58 @@
+//! Folding of the Japanese scripts: katakana into hiragana, and full/half-width forms into
+//! their usual width.
+
+/// Half-width katakana and punctuation from U+FF61 to U+FF9D, in order.
+const HALF_WIDTH: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+/// Katakana that take the voiced sound mark, e.g. カ => ガ.
+const VOICED: &str = "カキクケコサシスセソタチツテトハヒフヘホ";
+/// Katakana that take the semi-voiced sound mark, e.g. ハ => パ.
+const SEMI_VOICED: &str = "ハヒフヘホ";
+
+/// Full-width ASCII into ASCII, and half-width katakana into full-width katakana with their
+/// sound marks composed.
+pub fn fold_width(word: &str) -> String {
+    let mut folded = String::with_capacity(word.len());
+    for c in word.chars() {
+        match c {
+            '\u{3000}' => folded.push(' '),
+            '\u{FF01}'..='\u{FF5E}' => {
+                folded.push(std::char::from_u32(c as u32 - 0xFEE0).unwrap());
+            }
+            '\u{FF61}'..='\u{FF9D}' => {
+                folded.push(HALF_WIDTH.chars().nth(c as usize - 0xFF61).unwrap());
+            }
+            '\u{FF9E}' => match folded.pop() {
+                Some('ウ') => folded.push('ヴ'),
+                Some(last) if VOICED.contains(last) => {
+                    folded.push(std::char::from_u32(last as u32 + 1).unwrap());
+                }
+                last => {
+                    folded.extend(last);
+                    folded.push('゛');
+                }
+            },
+            '\u{FF9F}' => match folded.pop() {
+                Some(last) if SEMI_VOICED.contains(last) => {
+                    folded.push(std::char::from_u32(last as u32 + 2).unwrap());
+                }
+                last => {
+                    folded.extend(last);
+                    folded.push('゜');
+                }
+            },
+            _ => folded.push(c),
+        }
+    }
+    folded
+}
+
+/// Katakana into hiragana; those without a hiragana form (e.g. ヷ) and ー are kept.
+pub fn fold_kana(word: &str) -> String {
+    word.chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' | 'ヽ' | 'ヾ' => std::char::from_u32(c as u32 - 0x60).unwrap(),
+            _ => c,
+        })
+        .collect()
+}
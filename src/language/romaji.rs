@@ -0,0 +1,308 @@
+//! Romaji input for Japanese: Hepburn and kunrei-shiki spellings into hiragana, and the
+//! folding that lets both match the dictionary spelling.
+
+/// Spellings of the kana, Hepburn and kunrei-shiki alike; `n` and doubled consonants (small
+/// tsu) are handled by [`to_kana`].
+const KANA: &[(&str, &str)] = &[
+    ("a", "あ"),
+    ("i", "い"),
+    ("u", "う"),
+    ("e", "え"),
+    ("o", "お"),
+    ("ka", "か"),
+    ("ki", "き"),
+    ("ku", "く"),
+    ("ke", "け"),
+    ("ko", "こ"),
+    ("kya", "きゃ"),
+    ("kyu", "きゅ"),
+    ("kyo", "きょ"),
+    ("ga", "が"),
+    ("gi", "ぎ"),
+    ("gu", "ぐ"),
+    ("ge", "げ"),
+    ("go", "ご"),
+    ("gya", "ぎゃ"),
+    ("gyu", "ぎゅ"),
+    ("gyo", "ぎょ"),
+    ("sa", "さ"),
+    ("si", "し"),
+    ("shi", "し"),
+    ("su", "す"),
+    ("se", "せ"),
+    ("so", "そ"),
+    ("sha", "しゃ"),
+    ("shu", "しゅ"),
+    ("she", "しぇ"),
+    ("sho", "しょ"),
+    ("sya", "しゃ"),
+    ("syu", "しゅ"),
+    ("syo", "しょ"),
+    ("za", "ざ"),
+    ("zi", "じ"),
+    ("ji", "じ"),
+    ("zu", "ず"),
+    ("ze", "ぜ"),
+    ("zo", "ぞ"),
+    ("ja", "じゃ"),
+    ("ju", "じゅ"),
+    ("je", "じぇ"),
+    ("jo", "じょ"),
+    ("zya", "じゃ"),
+    ("zyu", "じゅ"),
+    ("zyo", "じょ"),
+    ("ta", "た"),
+    ("ti", "ち"),
+    ("chi", "ち"),
+    ("tu", "つ"),
+    ("tsu", "つ"),
+    ("te", "て"),
+    ("to", "と"),
+    ("cha", "ちゃ"),
+    ("chu", "ちゅ"),
+    ("che", "ちぇ"),
+    ("cho", "ちょ"),
+    ("tya", "ちゃ"),
+    ("tyu", "ちゅ"),
+    ("tyo", "ちょ"),
+    ("thi", "てぃ"),
+    ("tsa", "つぁ"),
+    ("da", "だ"),
+    ("di", "でぃ"),
+    ("dhi", "でぃ"),
+    ("du", "どぅ"),
+    ("de", "で"),
+    ("do", "ど"),
+    ("dyu", "でゅ"),
+    ("na", "な"),
+    ("ni", "に"),
+    ("nu", "ぬ"),
+    ("ne", "ね"),
+    ("no", "の"),
+    ("nya", "にゃ"),
+    ("nyu", "にゅ"),
+    ("nyo", "にょ"),
+    ("ha", "は"),
+    ("hi", "ひ"),
+    ("hu", "ふ"),
+    ("fu", "ふ"),
+    ("he", "へ"),
+    ("ho", "ほ"),
+    ("hya", "ひゃ"),
+    ("hyu", "ひゅ"),
+    ("hyo", "ひょ"),
+    ("fa", "ふぁ"),
+    ("fi", "ふぃ"),
+    ("fe", "ふぇ"),
+    ("fo", "ふぉ"),
+    ("ba", "ば"),
+    ("bi", "び"),
+    ("bu", "ぶ"),
+    ("be", "べ"),
+    ("bo", "ぼ"),
+    ("bya", "びゃ"),
+    ("byu", "びゅ"),
+    ("byo", "びょ"),
+    ("pa", "ぱ"),
+    ("pi", "ぴ"),
+    ("pu", "ぷ"),
+    ("pe", "ぺ"),
+    ("po", "ぽ"),
+    ("pya", "ぴゃ"),
+    ("pyu", "ぴゅ"),
+    ("pyo", "ぴょ"),
+    ("ma", "ま"),
+    ("mi", "み"),
+    ("mu", "む"),
+    ("me", "め"),
+    ("mo", "も"),
+    ("mya", "みゃ"),
+    ("myu", "みゅ"),
+    ("myo", "みょ"),
+    ("ya", "や"),
+    ("yu", "ゆ"),
+    ("ye", "いぇ"),
+    ("yo", "よ"),
+    ("ra", "ら"),
+    ("ri", "り"),
+    ("ru", "る"),
+    ("re", "れ"),
+    ("ro", "ろ"),
+    ("rya", "りゃ"),
+    ("ryu", "りゅ"),
+    ("ryo", "りょ"),
+    ("wa", "わ"),
+    ("wi", "うぃ"),
+    ("we", "うぇ"),
+    ("wo", "を"),
+    ("va", "ゔぁ"),
+    ("vi", "ゔぃ"),
+    ("vu", "ゔ"),
+    ("ve", "ゔぇ"),
+    ("vo", "ゔぉ"),
+    ("xa", "ぁ"),
+    ("xi", "ぃ"),
+    ("xu", "ぅ"),
+    ("xe", "ぇ"),
+    ("xo", "ぉ"),
+    ("xya", "ゃ"),
+    ("xyu", "ゅ"),
+    ("xyo", "ょ"),
+    ("xtu", "っ"),
+    ("-", "ー"),
+];
+
+/// Vowels with a macron or a circumflex, which spell long vowels.
+const LONG_VOWELS: &[(char, &str)] = &[
+    ('ā', "aa"),
+    ('â', "aa"),
+    ('ī', "ii"),
+    ('î', "ii"),
+    ('ū', "uu"),
+    ('û', "uu"),
+    ('ē', "ee"),
+    ('ê', "ee"),
+    ('ō', "ou"),
+    ('ô', "ou"),
+];
+
+/// Hiragana by their vowels, for reading ー and おう as long vowels.
+const VOWELS: &[(&str, char)] = &[
+    ("あぁかがさざただなはばぱまやゃらわゎ", 'あ'),
+    ("いぃきぎしじちぢにひびぴみりゐ", 'い'),
+    ("うぅくぐすずつづぬふぶぷむゆゅるゔ", 'う'),
+    ("えぇけげせぜてでねへべぺめれゑ", 'え'),
+    ("おぉこごそぞとどのほぼぽもよょろを", 'お'),
+];
+
+fn is_vowel(c: char) -> bool {
+    "aiueo".contains(c)
+}
+
+/// Romaji into hiragana; letters that spell no kana are kept as they are.
+///
+/// `n` before a consonant or at the end, `n'` and `nn` are ん; a doubled consonant (or `tch`)
+/// is a small tsu.
+pub fn to_kana(word: &str) -> String {
+    let mut spelled = String::with_capacity(word.len());
+    for c in word.chars() {
+        match LONG_VOWELS.iter().find(|(vowel, _)| *vowel == c) {
+            Some((_, long)) => spelled.push_str(long),
+            None => spelled.push(c),
+        }
+    }
+    let word = spelled;
+    let mut kana = String::with_capacity(word.len() * 3);
+    let mut rest = word.as_str();
+    while let Some(c) = rest.chars().next() {
+        let next = rest[c.len_utf8()..].chars().next();
+        if c == 'n' {
+            let after = rest[1..].chars().nth(1);
+            let skip = match next {
+                Some('\'') => Some(2),
+                Some('n') if !after.map_or(false, |c| is_vowel(c) || c == 'y') => Some(2),
+                Some(next) if is_vowel(next) || next == 'y' => None,
+                _ => Some(1),
+            };
+            if let Some(skip) = skip {
+                kana.push('ん');
+                rest = &rest[skip..];
+                continue;
+            }
+        }
+        if c.is_ascii_alphabetic()
+            && !is_vowel(c)
+            && (next == Some(c) || (c == 't' && next == Some('c')))
+        {
+            kana.push('っ');
+            rest = &rest[1..];
+            continue;
+        }
+        let spelling = KANA
+            .iter()
+            .filter(|(romaji, _)| rest.starts_with(romaji))
+            .max_by_key(|(romaji, _)| romaji.len());
+        match spelling {
+            Some((romaji, spelled)) => {
+                kana.push_str(spelled);
+                rest = &rest[romaji.len()..];
+            }
+            None => {
+                kana.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    kana
+}
+
+/// Reads alike the spellings romaji cannot tell apart: ー and おう as long vowels, ぢ and
+/// づ as じ and ず, and ディア (ティア) as the variant デア (テア).
+pub fn fold_readings(word: &str) -> String {
+    let mut folded = String::with_capacity(word.len());
+    let vowel = |c: Option<char>| {
+        c.and_then(|c| {
+            VOWELS
+                .iter()
+                .find(|(kana, _)| kana.contains(c))
+                .map(|(_, vowel)| *vowel)
+        })
+    };
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        let last = folded.chars().last();
+        let glide = (last == Some('て') || last == Some('で')) && chars.peek() == Some(&'あ');
+        match c {
+            'ー' => folded.push(vowel(last).unwrap_or(c)),
+            'う' if vowel(last) == Some('お') => folded.push('お'),
+            'ぢ' => folded.push('じ'),
+            'づ' => folded.push('ず'),
+            'ぃ' if glide => {}
+            _ => folded.push(c),
+        }
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syllabic_n() {
+        assert_eq!(to_kana("kan'i"), "かんい");
+        assert_eq!(to_kana("kanni"), "かんに");
+        assert_eq!(to_kana("kannon"), "かんのん");
+        assert_eq!(to_kana("konnnichiha"), "こんにちは");
+        assert_eq!(to_kana("shinbun"), "しんぶん");
+        assert_eq!(to_kana("hon"), "ほん");
+        assert_eq!(to_kana("kinyoubi"), "きにょうび");
+    }
+
+    #[test]
+    fn doubled_consonants() {
+        assert_eq!(to_kana("kitte"), "きって");
+        assert_eq!(to_kana("zasshi"), "ざっし");
+        assert_eq!(to_kana("matcha"), "まっちゃ");
+        assert_eq!(to_kana("macchi"), "まっち");
+    }
+
+    #[test]
+    fn hepburn_and_kunrei_shiki() {
+        assert_eq!(to_kana("shichi"), to_kana("siti"));
+        assert_eq!(to_kana("tsuzuku"), to_kana("tuzuku"));
+        assert_eq!(to_kana("fuji"), to_kana("huzi"));
+        assert_eq!(to_kana("jouhou"), to_kana("zyouhou"));
+    }
+
+    #[test]
+    fn long_vowels() {
+        assert_eq!(to_kana("kōhī"), "こうひい");
+        assert_eq!(to_kana("tôkyô"), "とうきょう");
+        assert_eq!(fold_readings("こーひー"), fold_readings(&to_kana("kōhī")));
+        assert_eq!(
+            fold_readings("おうばんぶるまい"),
+            fold_readings("おおばんぶるまい")
+        );
+    }
+}
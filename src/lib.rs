@@ -0,0 +1,88 @@
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate if_chain;
+extern crate clap;
+extern crate regex;
+extern crate toml;
+extern crate unicode_segmentation;
+extern crate ordinal;
+extern crate anyhow;
+extern crate boolinator;
+extern crate thiserror;
+//extern crate nazonazo_macros;
+
+use serenity::{
+    client::bridge::gateway::event::ShardStageUpdateEvent,
+    gateway::ConnectionStage,
+    model::{channel::Message, gateway::Ready},
+    prelude::*,
+};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+pub mod bot;
+pub mod chat;
+pub mod commands;
+pub mod dictionary;
+pub mod error;
+#[cfg(feature = "test-harness")]
+pub mod harness;
+pub mod history;
+pub mod language;
+pub mod rating;
+pub mod sandbox;
+pub mod server;
+pub mod settings;
+pub mod sort;
+
+use commands::router::Router;
+use serenity::model::id::ChannelId;
+
+/// User id of the bot; mentioning it works as a prefix.
+pub const BOT_ID: u64 = 621402474527588352;
+
+#[macro_export]
+macro_rules! try_say {
+    ($chat: expr, $msg: expr, $response: expr) => {
+        ($chat).say(($msg).channel, ($response).to_string())
+    };
+}
+
+pub struct Handler {
+    router: Router,
+}
+
+impl Handler {
+    pub fn new(router: Router) -> Handler {
+        Handler { router }
+    }
+}
+
+impl EventHandler for Handler {
+    fn ready(&self, ctx: Context, ready: Ready) {
+        ChannelId::from(621544952299782144_u64)
+            .say(&ctx, "botがDiscordとの接続を完了しました。")
+            .expect("fail to send");
+        println!("{} is connected!", ready.user.name);
+        server::admin::GATEWAY_CONNECTED.store(true, Ordering::SeqCst);
+    }
+
+    fn message(&self, ctx: Context, msg: Message) {
+        let chat: chat::Transport = Arc::new(chat::Discord(ctx.http.clone()));
+        let mut message = chat::Message::from(&msg);
+        if let Some(guild) = msg.guild(&ctx.cache) {
+            let guild = guild.read();
+            message.administrator = guild.member_permissions(msg.author.id).administrator();
+            if let Some(member) = guild.members.get(&msg.author.id) {
+                message.roles = member.roles.iter().map(|role| *role.as_u64()).collect();
+            }
+        }
+        self.router.dispatch(&chat, &message);
+    }
+
+    fn shard_stage_update(&self, _: Context, event: ShardStageUpdateEvent) {
+        server::admin::GATEWAY_CONNECTED
+            .store(event.new == ConnectionStage::Connected, Ordering::SeqCst);
+    }
+}
@@ -1,138 +1,44 @@
-#[macro_use]
-extern crate lazy_static;
-#[macro_use]
-extern crate if_chain;
-#[macro_use]
-extern crate custom_derive;
-#[macro_use]
-extern crate enum_derive;
-extern crate clap;
-extern crate regex;
-extern crate toml;
-extern crate unicode_segmentation;
-extern crate ordinal;
-extern crate anyhow;
-extern crate boolinator;
-extern crate thiserror;
-//extern crate nazonazo_macros;
-
-use regex::Regex;
-use serenity::{
-    client::Client, framework::standard::StandardFramework, model::gateway::Ready, prelude::*,
-};
+use clap::{App, Arg};
+use mitama_test_bot::chat::{Discord, Transport};
+use mitama_test_bot::commands::router::Router;
+use mitama_test_bot::{language, server, settings, Handler, BOT_ID};
+use serenity::{client::Client, http::Http};
+use std::collections::HashSet;
 use std::env;
-
-pub mod bot;
-pub mod commands;
-pub mod dictionary;
-pub mod error;
-pub mod settings;
-pub mod sort;
-use sort::Sorted;
-
-use commands::{executors, facade};
-use serenity::model::id::{ChannelId, UserId};
-
-#[macro_export]
-macro_rules! try_say {
-    ($ctx: expr, $msg: expr, $response: expr) => {
-        if let Err(why) = ($msg).channel_id.say(&($ctx), $response) {
-            println!("{}", why);
-        }
-    };
-}
-
-struct Handler;
-
-impl EventHandler for Handler {
-    fn ready(&self, ctx: Context, ready: Ready) {
-        ChannelId::from(621544952299782144_u64)
-            .say(&ctx, "botがDiscordとの接続を完了しました。")
-            .expect("fail to send");
-        println!("{} is connected!", ready.user.name);
-    }
-}
+use std::sync::Arc;
 
 fn main() {
+    let matches = App::new("mitama-test-bot")
+        .arg(
+            Arg::with_name("settings-dir")
+                .long("settings-dir")
+                .value_name("DIR")
+                .env("SETTINGS_DIR")
+                .help("Directory of the settings, contest history and ratings")
+                .default_value(settings::DEFAULT_DIR),
+        )
+        .get_matches();
+    // read by `settings::DIR` on first use.
+    env::set_var("SETTINGS_DIR", matches.value_of("settings-dir").unwrap());
     println!("hello!");
+    settings::init();
+    lazy_static::initialize(&language::LANGUAGES);
     // Login with a bot token from the environment
-    let mut client = Client::new(&env::var("DISCORD_TOKEN").expect("token"), Handler)
+    let token = env::var("DISCORD_TOKEN").expect("token");
+    let owners = match Http::new_with_token(&token).get_current_application_info() {
+        Ok(info) => {
+            let mut set = HashSet::new();
+            set.insert(*info.owner.id.as_u64());
+            set
+        }
+        Err(why) => panic!("Couldn't get application info: {:?}", why),
+    };
+    let mut client = Client::new(&token, Handler::new(Router::new(owners, BOT_ID)))
         .expect("Error creating client");
-    client.with_framework(
-        StandardFramework::new()
-            .configure(|c| {
-                c.dynamic_prefix(|_, msg| {
-                    Some(
-                        settings::SETTINGS
-                            .lock()
-                            .unwrap()
-                            .prefix
-                            .dynamic
-                            .get(&msg.channel_id.as_u64().to_string())
-                            .cloned()
-                            .unwrap_or_else(|| "~".to_string()),
-                    )
-                })
-                .on_mention(Some(UserId::from(621402474527588352)))
-                .allow_dm(true)
-                .no_dm_prefix(true)
-            }) // set the bot's prefix to "~"
-            .bucket("basic", |b| b.delay(1).time_span(0).limit(1))
-            .bucket("long", |b| b.delay(1).time_span(2).limit(1))
-            .before(|ctx, msg, command_name| {
-                if command_name == "enable" {
-                    return true;
-                }
-                if !settings::SETTINGS
-                    .lock()
-                    .unwrap()
-                    .channel
-                    .enabled
-                    .contains(msg.channel_id.as_u64())
-                {
-                    return false;
-                }
-                if facade::QUIZ_COMMANDS_REGEX.is_match(&command_name.to_string()) {
-                    match &*bot::QUIZ.lock().unwrap() {
-                        bot::Status::Holding(ref ans, ..) => {
-                            try_say!(
-                                ctx,
-                                msg,
-                                format!("前回の出題が解かれていません\n問題: `{}`", ans.sorted())
-                            );
-                            false
-                        }
-                        bot::Status::Contesting(ref ans, ..) => {
-                            try_say!(
-                                ctx,
-                                msg,
-                                format!("現在コンテスト中です\n問題: `{}`", ans.sorted())
-                            );
-                            false
-                        }
-                        bot::Status::StandingBy => true,
-                    }
-                } else {
-                    true
-                }
-            })
-            .normal_message(|ctx, msg| {
-                println!("{}", msg.author.id);
-                if !msg.author.bot {
-                    let re = Regex::new(r"^kick\(.*\);$").unwrap();
-                    if re.is_match(&msg.content) {
-                        println!("{:?}", executors::kick(ctx, msg));
-                        return;
-                    }
-                    executors::answer_check(ctx, msg);
-                }
-            })
-            .group(&commands::facade::QUIZ_GROUP)
-            .group(&commands::facade::CONTEST_GROUP)
-            .group(&commands::facade::SETTINGS_GROUP)
-            .group(&commands::facade::EXTRA_GROUP)
-            .help(&commands::facade::NAZONAZO_HELP),
-    );
+    let chat: Transport = Arc::new(Discord(client.cache_and_http.http.clone()));
+    if let Err(why) = server::start(chat) {
+        println!("Could not start the HTTP server: {:?}", why);
+    }
     // start listening for events by starting a single shard
     if let Err(why) = client.start() {
         println!("An error occurred while running the client: {:?}", why);
@@ -0,0 +1,230 @@
+use super::bot::{ContestData, Contestant};
+use super::error::Lock;
+use super::settings;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Player {
+    /// Display name the player was last rated with.
+    pub name: String,
+    pub rating: f64,
+    pub contests: u32,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Player {
+            name: String::new(),
+            rating: INITIAL_RATING,
+            contests: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingChange {
+    pub name: String,
+    pub old: f64,
+    pub new: f64,
+}
+
+impl RatingChange {
+    pub fn as_string(&self) -> String {
+        format!(
+            "{}: {:.0} -> {:.0} ({:+.0})",
+            self.name,
+            self.old,
+            self.new,
+            self.new - self.old
+        )
+    }
+}
+
+/// Players by user id; the ids are kept as strings since TOML keys must be.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct Ratings {
+    pub players: IndexMap<String, Player>,
+}
+
+impl Ratings {
+    pub fn get(&self, id: u64) -> Option<&Player> {
+        self.players.get(&id.to_string())
+    }
+
+    /// The player last rated with the display name.
+    pub fn find(&self, name: &str) -> Option<&Player> {
+        self.players.values().find(|player| player.name == name)
+    }
+
+    /// Applies a multiplayer Elo update from the final standings.
+    ///
+    /// Every pair of participants is treated as a single game; players sharing the same
+    /// `ContestData::key` draw. Questions given up are not rated.
+    pub fn update(&mut self, standings: &[(Contestant, ContestData)]) -> Vec<RatingChange> {
+        let rated = standings
+            .iter()
+            .filter_map(|(contestant, data)| match contestant {
+                Contestant::Player(id) => Some((id.to_string(), data)),
+                Contestant::GaveUp => None,
+            })
+            .collect::<Vec<_>>();
+        if rated.len() < 2 {
+            return Vec::new();
+        }
+        let k = K_FACTOR / (rated.len() - 1) as f64;
+        let current = rated
+            .iter()
+            .map(|(id, _)| {
+                self.players
+                    .get(id)
+                    .map_or(INITIAL_RATING, |player| player.rating)
+            })
+            .collect::<Vec<_>>();
+        let changes = rated
+            .iter()
+            .enumerate()
+            .map(|(i, (_, data))| {
+                let delta = rated
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(j, (_, other))| {
+                        let diff = (current[j] - current[i]) / 400.0;
+                        let expected = 1.0 / (1.0 + 10f64.powf(diff));
+                        let actual = match data.key().cmp(&other.key()) {
+                            std::cmp::Ordering::Less => 1.0,
+                            std::cmp::Ordering::Equal => 0.5,
+                            std::cmp::Ordering::Greater => 0.0,
+                        };
+                        k * (actual - expected)
+                    })
+                    .sum::<f64>();
+                RatingChange {
+                    name: data.name.clone(),
+                    old: current[i],
+                    new: current[i] + delta,
+                }
+            })
+            .collect::<Vec<_>>();
+        for ((id, _), change) in rated.iter().zip(&changes) {
+            let player = self
+                .players
+                .entry(id.clone())
+                .or_insert_with(Player::default);
+            player.name = change.name.clone();
+            player.rating = change.new;
+            player.contests += 1;
+        }
+        changes
+    }
+
+    /// Players sorted by rating in descending order.
+    pub fn ranking(&self) -> Vec<&Player> {
+        self.players
+            .values()
+            .sorted_by(|lhs, rhs| rhs.rating.partial_cmp(&lhs.rating).unwrap())
+            .collect()
+    }
+}
+
+lazy_static! {
+    pub(crate) static ref RATINGS: Arc<Mutex<settings::Stored<Ratings>>> = Arc::new(Mutex::new(
+        settings::Stored::load(settings::path("rating.toml"))
+    ));
+}
+
+/// Rates a finished contest and writes the ratings back.
+pub(crate) fn rate(standings: &[(Contestant, ContestData)]) -> anyhow::Result<Vec<RatingChange>> {
+    let mut ratings = RATINGS.locked()?;
+    let changes = ratings.update(standings);
+    if !changes.is_empty() {
+        ratings.save()?;
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standing(contestant: Contestant, name: &str, time: &[f32]) -> (Contestant, ContestData) {
+        (
+            contestant,
+            ContestData {
+                name: name.to_string(),
+                time: time.to_vec(),
+            },
+        )
+    }
+
+    #[test]
+    fn winner_takes_from_loser() {
+        let mut ratings = Ratings::default();
+        let changes = ratings.update(&[
+            standing(Contestant::Player(1), "alice", &[1.0, 2.0]),
+            standing(Contestant::Player(2), "bob", &[3.0]),
+        ]);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].name, "alice");
+        assert!((changes[0].new - (INITIAL_RATING + K_FACTOR / 2.0)).abs() < 1e-9);
+        assert!((changes[1].new - (INITIAL_RATING - K_FACTOR / 2.0)).abs() < 1e-9);
+        assert_eq!(ratings.get(1).unwrap().contests, 1);
+        assert_eq!(ratings.find("bob").unwrap().rating, changes[1].new);
+    }
+
+    #[test]
+    fn equal_scores_draw() {
+        let mut ratings = Ratings::default();
+        let changes = ratings.update(&[
+            standing(Contestant::Player(1), "alice", &[2.0]),
+            standing(Contestant::Player(2), "bob", &[2.0]),
+        ]);
+        assert!(changes.iter().all(|change| change.new == change.old));
+    }
+
+    #[test]
+    fn questions_given_up_are_not_rated() {
+        let mut ratings = Ratings::default();
+        let changes = ratings.update(&[
+            standing(Contestant::GaveUp, "~giveup", &[1.0, 1.0]),
+            standing(Contestant::Player(1), "alice", &[2.0]),
+            standing(Contestant::Player(2), "bob", &[3.0]),
+        ]);
+        let names = changes.iter().map(|change| change.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), vec!["alice", "bob"]);
+        assert_eq!(ratings.players.len(), 2);
+    }
+
+    #[test]
+    fn single_player_is_not_rated() {
+        let mut ratings = Ratings::default();
+        let changes = ratings.update(&[
+            standing(Contestant::Player(1), "alice", &[2.0]),
+            standing(Contestant::GaveUp, "~giveup", &[1.0]),
+        ]);
+        assert!(changes.is_empty());
+        assert!(ratings.players.is_empty());
+    }
+
+    #[test]
+    fn players_are_keyed_by_id() {
+        let mut ratings = Ratings::default();
+        let standings = |name: &str| {
+            vec![
+                standing(Contestant::Player(1), name, &[1.0]),
+                standing(Contestant::Player(2), "bob", &[2.0]),
+            ]
+        };
+        ratings.update(&standings("alice"));
+        ratings.update(&standings("~alice"));
+        let player = ratings.get(1).unwrap();
+        assert_eq!(player.name, "~alice");
+        assert_eq!(player.contests, 2);
+    }
+}
@@ -0,0 +1,286 @@
+//! Compiles and runs untrusted programs for `kick(...);`.
+//!
+//! Both `rustc` and the program run as the user `SANDBOX_UID` in fresh PID, network, IPC and UTS
+//! namespaces with caps on processes, memory and file size, so that they can neither see the bot
+//! (and its tokens) nor outlive their time limit. The filesystem is not isolated: they can read
+//! whatever `SANDBOX_UID` may read and write to world-writable paths such as `/tmp`, up to the
+//! file size cap. `kick` is disabled unless `SANDBOX_UID` is set; the bot must run as root for
+//! `unshare` and `setpriv` from util-linux.
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use threadpool::ThreadPool;
+
+/// Number of programs compiled and run at once.
+pub const WORKERS: usize = 2;
+/// Number of submissions waiting for a worker before new ones are rejected.
+pub const MAX_QUEUE: usize = 8;
+
+/// Environment variables passed to `rustc`; everything else is cleared.
+const COMPILER_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+    "CARGO_HOME",
+];
+
+/// User (and group) id the sandboxed processes run as; `kick` is disabled without it.
+pub const UID_VAR: &str = "SANDBOX_UID";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub compile: Duration,
+    pub run: Duration,
+    /// Bytes kept from each of stdout and stderr.
+    pub output: usize,
+    /// Virtual memory of `rustc` in KiB.
+    pub compiler_memory: u64,
+    /// Virtual memory of the compiled program in KiB.
+    pub memory: u64,
+    /// Size of each file written by `rustc` or the program in KiB.
+    pub file_size: u64,
+    /// Processes (and threads) of the sandbox user at once, `rustc` included.
+    pub processes: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            compile: Duration::from_secs(10),
+            run: Duration::from_secs(2),
+            output: 16 * 1024,
+            compiler_memory: 2 * 1024 * 1024,
+            memory: 256 * 1024,
+            file_size: 64 * 1024,
+            processes: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Compile,
+    Run,
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Success {
+        stdout: String,
+    },
+    Failure {
+        stage: Stage,
+        stderr: String,
+    },
+    TimedOut {
+        stage: Stage,
+    },
+    /// The queue is full; the program was not compiled.
+    Busy,
+}
+
+/// Output of a finished process, each stream cut at the output limit.
+struct Output {
+    success: bool,
+    stdout: String,
+    stderr: String,
+}
+
+lazy_static! {
+    static ref UID: Option<u32> = match env::var(UID_VAR) {
+        Ok(uid) => uid
+            .parse()
+            .map_err(|_| println!("{} must be a user id, not `{}`", UID_VAR, uid))
+            .ok(),
+        Err(_) => None,
+    };
+    static ref POOL: Mutex<ThreadPool> =
+        Mutex::new(ThreadPool::with_name("sandbox".to_string(), WORKERS));
+}
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether `SANDBOX_UID` is set, i.e. programs may be run at all.
+pub fn enabled() -> bool {
+    UID.is_some()
+}
+
+/// Queues `source` for compilation and execution, handing the outcome to `callback` on a
+/// worker thread.
+pub fn submit<F>(source: String, limits: Limits, callback: F)
+where
+    F: FnOnce(io::Result<Outcome>) + Send + 'static,
+{
+    let pool = POOL.lock().unwrap();
+    if pool.queued_count() >= MAX_QUEUE {
+        drop(pool);
+        callback(Ok(Outcome::Busy));
+        return;
+    }
+    pool.execute(move || callback(run(&source, limits)));
+}
+
+/// Compiles and runs `source` in a fresh temporary directory.
+pub fn run(source: &str, limits: Limits) -> io::Result<Outcome> {
+    let uid = UID.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("the sandbox is disabled; set {}", UID_VAR),
+        )
+    })?;
+    let dir = TempDir::new()?;
+    let mut src = File::create(dir.path().join("main.rs"))?;
+    src.write_all(source.as_bytes())?;
+    src.flush()?;
+    // the sandbox user writes the executable next to the source.
+    fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o777))?;
+
+    let processes = format!("--nproc={}", limits.processes);
+    let file_size = format!("--fsize={}", limits.file_size * 1024);
+    let compiler_memory = format!("--as={}", limits.compiler_memory * 1024);
+    let mut rustc = isolated(uid, &[&processes, &compiler_memory, &file_size]);
+    rustc
+        .args(&["rustc", "main.rs", "-o", "main"])
+        .current_dir(dir.path());
+    for key in COMPILER_ENV {
+        if let Some(value) = std::env::var_os(key) {
+            rustc.env(key, value);
+        }
+    }
+    let compiled = match execute(rustc, limits.compile, limits.output)? {
+        Some(output) => output,
+        None => {
+            return Ok(Outcome::TimedOut {
+                stage: Stage::Compile,
+            })
+        }
+    };
+    if !compiled.success {
+        return Ok(Outcome::Failure {
+            stage: Stage::Compile,
+            stderr: compiled.stderr,
+        });
+    }
+
+    let memory = format!("--as={}", limits.memory * 1024);
+    let mut program = isolated(uid, &[&processes, &memory, &file_size]);
+    program.arg("./main").current_dir(dir.path());
+    Ok(match execute(program, limits.run, limits.output)? {
+        Some(output) if output.success => Outcome::Success {
+            stdout: output.stdout,
+        },
+        Some(output) => Outcome::Failure {
+            stage: Stage::Run,
+            stderr: output.stderr,
+        },
+        None => Outcome::TimedOut { stage: Stage::Run },
+    })
+}
+
+/// `unshare`, `setpriv` and `prlimit` in front of the command to be appended.
+///
+/// Killing the wrapper kills the first process of the PID namespace (`--kill-child`), which
+/// takes down every process in it. That process is a root shell waiting for the command, as
+/// the death signal would not survive the change of user by `setpriv`.
+fn isolated(uid: u32, rlimits: &[&str]) -> Command {
+    let mut command = Command::new("unshare");
+    command
+        .args(&["--fork", "--pid", "--mount-proc", "--net", "--ipc", "--uts"])
+        .args(&["--kill-child", "--"])
+        .args(&["/bin/sh", "-c", "\"$@\"; exit $?", "sh", "setpriv"])
+        .arg(format!("--reuid={}", uid))
+        .arg(format!("--regid={}", uid))
+        .args(&["--clear-groups", "--no-new-privs", "--", "prlimit"])
+        .args(rlimits)
+        .arg("--")
+        .env_clear()
+        .env("PATH", "/usr/local/bin:/usr/bin:/bin");
+    command
+}
+
+/// Runs the command, killing it and returning `None` once `timeout` has passed.
+fn execute(mut command: Command, timeout: Duration, cap: usize) -> io::Result<Option<Output>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = capture(child.stdout.take().unwrap(), cap);
+    let stderr = capture(child.stderr.take().unwrap(), cap);
+    let status = match wait_timeout(&mut child, timeout)? {
+        Some(status) => status,
+        None => {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+    };
+    Ok(Some(Output {
+        success: status.success(),
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+fn wait_timeout(
+    child: &mut Child,
+    timeout: Duration,
+) -> io::Result<Option<std::process::ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Reads the stream to the end on another thread, keeping at most `cap` bytes.
+fn capture<R: Read + Send + 'static>(stream: R, cap: usize) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut stream = stream;
+        let _ = stream.by_ref().take(cap as u64).read_to_end(&mut kept);
+        // drain the rest so that the child never blocks on a full pipe.
+        let _ = io::copy(&mut stream, &mut io::sink());
+        String::from_utf8_lossy(&kept).into_owned()
+    })
+}
+
+/// Directory removed with its contents when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> io::Result<TempDir> {
+        let path = std::env::temp_dir().join(format!(
+            "nazonazo-sandbox-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir(&path)?;
+        Ok(TempDir(path))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.0) {
+            println!("could not remove {}: {}", self.0.display(), err);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+pub mod admin;
+pub mod graphql;
+pub mod http;
+
+use self::http::{Request, Response};
+use super::chat::Transport;
+use std::env;
+use std::io::{self, BufReader, Read};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Address listened on unless `HTTP_ADDR` is set.
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+/// Connections served at once; further ones are answered with 503.
+pub const MAX_CONNECTIONS: usize = 32;
+
+/// Time a client has to send its whole request, and to read the response.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a connection being served until dropped.
+struct Connection;
+
+impl Connection {
+    fn open() -> Option<Connection> {
+        if CONNECTIONS.fetch_add(1, Ordering::SeqCst) < MAX_CONNECTIONS {
+            Some(Connection)
+        } else {
+            CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+            None
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+lazy_static! {
+    static ref SCHEMA: graphql::Schema = graphql::schema();
+}
+
+/// Serves `/healthz`, `/channels` and `/graphql` on `HTTP_ADDR` in the background.
+///
+/// Requests carrying `Authorization: Bearer $ADMIN_TOKEN` are treated as admin; without
+/// `ADMIN_TOKEN` nobody is.
+pub fn start(chat: Transport) -> io::Result<()> {
+    let addr = env::var("HTTP_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string());
+    let listener = TcpListener::bind(&addr)?;
+    println!("HTTP server is listening on {}", addr);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let connection = match Connection::open() {
+                        Some(connection) => connection,
+                        None => {
+                            let busy = Response::text(503, "too many connections");
+                            if let Err(err) = busy.write_to(&mut &stream) {
+                                println!("{}", err);
+                            }
+                            continue;
+                        }
+                    };
+                    let chat = chat.clone();
+                    std::thread::spawn(move || {
+                        if let Err(err) = serve(stream, chat) {
+                            println!("{}", err);
+                        }
+                        drop(connection);
+                    });
+                }
+                Err(err) => println!("{}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Reads from the stream until `deadline`, however slowly the client sends, so that a
+/// connection cannot be held longer than that.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request was not received in time",
+            ));
+        }
+        self.stream.set_read_timeout(Some(self.deadline - now))?;
+        (&mut &*self.stream).read(buf)
+    }
+}
+
+fn serve(stream: TcpStream, chat: Transport) -> io::Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(Deadline {
+        stream: &stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+    let request = match Request::read(&mut reader) {
+        Ok(request) => request,
+        Err(err) => return Response::text(400, err.to_string()).write_to(&mut &stream),
+    };
+    route(&request, chat).write_to(&mut &stream)
+}
+
+fn is_admin(request: &Request) -> bool {
+    match (env::var("ADMIN_TOKEN"), request.bearer()) {
+        (Ok(token), Some(bearer)) if !token.is_empty() => {
+            constant_time_eq(token.as_bytes(), bearer.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+/// Compares without returning early, so that the time taken does not tell how much of a
+/// guessed token is right.
+fn constant_time_eq(lhs: &[u8], rhs: &[u8]) -> bool {
+    lhs.len() == rhs.len() && lhs.iter().zip(rhs).fold(0, |diff, (l, r)| diff | (l ^ r)) == 0
+}
+
+fn route(request: &Request, chat: Transport) -> Response {
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["healthz"]) => admin::healthz(),
+        ("GET", ["channels"]) => admin::channels(),
+        ("POST", ["channels", channel, action]) if *action == "enable" || *action == "disable" => {
+            if is_admin(request) {
+                admin::set_enabled(channel, *action == "enable")
+            } else {
+                Response::text(401, "admin token is required")
+            }
+        }
+        ("GET", ["graphql"]) => {
+            Response::html(juniper::http::graphiql::graphiql_source("/graphql"))
+        }
+        ("POST", ["graphql"]) => execute(request, chat),
+        (_, ["graphql"]) => Response::text(405, "method not allowed"),
+        _ => Response::text(404, "not found"),
+    }
+}
+
+fn execute(request: &Request, chat: Transport) -> Response {
+    let query = match serde_json::from_slice::<juniper::http::GraphQLRequest>(&request.body) {
+        Ok(query) => query,
+        Err(err) => return Response::text(400, err.to_string()),
+    };
+    let context = graphql::Context {
+        admin: is_admin(request),
+        chat,
+    };
+    let response = query.execute(&SCHEMA, &context);
+    match serde_json::to_string(&response) {
+        Ok(body) => Response::json(if response.is_ok() { 200 } else { 400 }, body),
+        Err(err) => Response::text(400, err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_compare() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secre"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+
+    #[test]
+    fn slow_requests_time_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        std::thread::spawn(move || {
+            let mut client = &client;
+            let _ = io::Write::write_all(&mut client, b"GET /healthz HTTP/1.1\r\nX: ");
+            // a header value dripping in forever.
+            while io::Write::write_all(&mut client, b"y").is_ok() {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+        let mut reader = BufReader::new(Deadline {
+            stream: &stream,
+            deadline: Instant::now() + Duration::from_millis(200),
+        });
+        let started = Instant::now();
+        let err = Request::read(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,75 @@
+use super::super::error::Lock;
+use super::super::language::LANGUAGES;
+use super::super::settings::{self, sync_setting};
+use super::http::Response;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the Discord gateway is connected; updated by the event handler.
+pub static GATEWAY_CONNECTED: AtomicBool = AtomicBool::new(false);
+
+/// `200` when every dictionary has questions and the gateway is connected, `503` otherwise.
+///
+/// Dictionaries that cannot be read are listed with a `null` length.
+pub fn healthz() -> Response {
+    let dictionaries = LANGUAGES
+        .langs()
+        .map(|lang| {
+            let len = lang.dictionary().ok().map(|dic| dic.len());
+            (lang.as_code().to_string(), json!(len))
+        })
+        .collect::<serde_json::Map<_, _>>();
+    let loaded = dictionaries
+        .values()
+        .all(|len| len.as_u64().map_or(false, |len| len > 0));
+    let gateway = GATEWAY_CONNECTED.load(Ordering::SeqCst);
+    let healthy = loaded && gateway;
+    Response::json(
+        if healthy { 200 } else { 503 },
+        json!({
+            "status": if healthy { "ok" } else { "unavailable" },
+            "gateway": gateway,
+            "dictionaries": dictionaries,
+        })
+        .to_string(),
+    )
+}
+
+/// Enabled channels with their prefixes, including those enabled by their guild.
+pub fn channels() -> Response {
+    let settings = match settings::SETTINGS.locked() {
+        Ok(settings) => settings,
+        Err(err) => return Response::text(500, err.to_string()),
+    };
+    let channels = settings
+        .channels()
+        .filter(|(id, guild)| settings.is_enabled(*id, *guild))
+        .map(|(id, guild)| {
+            json!({
+                "id": id.to_string(),
+                "guild": guild.map(|guild| guild.to_string()),
+                "prefix": settings.prefix(id, guild),
+            })
+        })
+        .collect::<Vec<_>>();
+    Response::json(200, json!(channels).to_string())
+}
+
+/// Enables or disables the channel as `~enable` and `~disable` do.
+pub fn set_enabled(channel: &str, enabled: bool) -> Response {
+    let id = match channel.parse::<u64>() {
+        Ok(id) => id,
+        Err(_) => return Response::text(400, format!("invalid channel id `{}`", channel)),
+    };
+    match settings::SETTINGS.locked() {
+        Ok(mut settings) => settings.channel_mut(id, None).enabled = Some(enabled),
+        Err(err) => return Response::text(500, err.to_string()),
+    }
+    if let Err(err) = sync_setting() {
+        println!("{:?}", err);
+    }
+    Response::json(
+        200,
+        json!({ "id": id.to_string(), "enabled": enabled }).to_string(),
+    )
+}
@@ -0,0 +1,298 @@
+use super::super::bot::{self, ContestData, Contestant, Status};
+use super::super::chat::Transport;
+use super::super::commands::executors;
+use super::super::dictionary::Filter;
+use super::super::error::Lock;
+use super::super::history::{self, ContestRecord, ProblemRecord};
+use super::super::language::LANGUAGES;
+use super::super::settings;
+use super::super::sort::Sorted;
+use juniper::{FieldError, FieldResult, RootNode};
+
+/// Per-request data of the GraphQL schema.
+pub struct Context {
+    /// Whether the request carries the admin token; required by mutations.
+    pub admin: bool,
+    pub chat: Transport,
+}
+
+impl juniper::Context for Context {}
+
+pub type Schema = RootNode<'static, Query, Mutation>;
+
+pub fn schema() -> Schema {
+    Schema::new(Query, Mutation)
+}
+
+#[derive(juniper::GraphQLEnum)]
+enum State {
+    StandingBy,
+    Holding,
+    Contesting,
+}
+
+#[derive(juniper::GraphQLObject)]
+struct Progress {
+    current: i32,
+    total: i32,
+}
+
+/// Accepted answers of a player in a contest.
+#[derive(juniper::GraphQLObject)]
+struct Score {
+    name: String,
+    accepted: i32,
+    /// Average time to solve in seconds.
+    average: f64,
+}
+
+/// Quiz state of a channel (or DM).
+#[derive(juniper::GraphQLObject)]
+struct Channel {
+    id: String,
+    state: State,
+    /// The sorted puzzle; the answer is never exposed.
+    puzzle: Option<String>,
+    language: Option<String>,
+    /// Seconds since the question was posed.
+    elapsed: Option<f64>,
+    progress: Option<Progress>,
+    standings: Vec<Score>,
+}
+
+#[derive(juniper::GraphQLObject)]
+struct DictionaryInfo {
+    language: String,
+    symbol: String,
+    /// Number of questions.
+    len: i32,
+    /// Number of words accepted as answers, if the dictionary has a separate answer list.
+    full_len: Option<i32>,
+}
+
+#[derive(juniper::GraphQLObject)]
+struct Problem {
+    language: String,
+    question: String,
+    answer: String,
+    solver: Option<String>,
+    elapsed: Option<f64>,
+    gave_up: bool,
+}
+
+#[derive(juniper::GraphQLObject)]
+struct Contest {
+    id: i32,
+    /// RFC 3339 date of the end of the contest.
+    date: String,
+    channel: String,
+    number: i32,
+    languages: Vec<String>,
+    problems: Vec<Problem>,
+    standings: Vec<Score>,
+}
+
+impl From<&(Contestant, ContestData)> for Score {
+    fn from((_, data): &(Contestant, ContestData)) -> Self {
+        Score {
+            name: data.name.clone(),
+            accepted: data.time.len() as i32,
+            average: (data.time.iter().sum::<f32>() / data.time.len().max(1) as f32) as f64,
+        }
+    }
+}
+
+impl From<&ProblemRecord> for Problem {
+    fn from(record: &ProblemRecord) -> Self {
+        Problem {
+            language: record.lang.clone(),
+            question: record.question.clone(),
+            answer: record.answer.clone(),
+            solver: record.solver.clone(),
+            elapsed: record.elapsed.map(f64::from),
+            gave_up: record.gave_up,
+        }
+    }
+}
+
+impl Contest {
+    fn new(id: usize, record: &ContestRecord) -> Contest {
+        Contest {
+            id: id as i32,
+            date: record.date.to_rfc3339(),
+            channel: record.channel.to_string(),
+            number: record.number as i32,
+            languages: record.languages.clone(),
+            problems: record.problems.iter().map(Problem::from).collect(),
+            standings: record.standings().iter().map(Score::from).collect(),
+        }
+    }
+}
+
+fn channel_of(id: u64, session: &bot::Session) -> Channel {
+    let (state, puzzle, lang, progress) = match &session.quiz {
+        Status::StandingBy => (State::StandingBy, None, None, None),
+        Status::Holding(ans, lang, _) => (State::Holding, Some(ans), Some(*lang), None),
+        Status::Contesting(ans, lang, (current, total), _) => (
+            State::Contesting,
+            Some(ans),
+            Some(*lang),
+            Some(Progress {
+                current: *current as i32,
+                total: *total as i32,
+            }),
+        ),
+    };
+    Channel {
+        id: id.to_string(),
+        state,
+        puzzle: puzzle.map(|ans| ans.sorted()),
+        language: lang.map(|lang| lang.as_code().to_string()),
+        elapsed: session.quiz.elapsed().map(f64::from),
+        progress,
+        standings: bot::standings(&session.contest_result)
+            .iter()
+            .map(Score::from)
+            .collect(),
+    }
+}
+
+fn parse_channel(id: &str) -> FieldResult<u64> {
+    id.parse::<u64>().map_err(|_| {
+        FieldError::new(
+            format!("invalid channel id `{}`", id),
+            juniper::Value::null(),
+        )
+    })
+}
+
+fn require_admin(context: &Context) -> FieldResult<()> {
+    if context.admin {
+        Ok(())
+    } else {
+        Err(FieldError::new(
+            "admin token is required",
+            juniper::Value::null(),
+        ))
+    }
+}
+
+pub struct Query;
+
+#[juniper::object(Context = Context)]
+impl Query {
+    /// Channels with a question posed or a contest running.
+    fn channels() -> FieldResult<Vec<Channel>> {
+        let sessions = bot::SESSIONS.locked()?.clone();
+        sessions
+            .iter()
+            .map(|(id, session)| Ok(channel_of(*id, &*session.locked()?)))
+            .collect()
+    }
+
+    fn channel(id: String) -> FieldResult<Channel> {
+        let id = parse_channel(&id)?;
+        Ok(match bot::find_session(id)? {
+            Some(session) => channel_of(id, &*session.locked()?),
+            None => channel_of(id, &bot::Session::default()),
+        })
+    }
+
+    fn dictionaries() -> FieldResult<Vec<DictionaryInfo>> {
+        LANGUAGES
+            .langs()
+            .map(|lang| {
+                let dic = lang.dictionary()?;
+                Ok(DictionaryInfo {
+                    language: lang.as_code().to_string(),
+                    symbol: lang.as_symbol(),
+                    len: dic.len() as i32,
+                    full_len: dic.full_len().map(|len| len as i32),
+                })
+            })
+            .collect()
+    }
+
+    /// Finished contests, optionally only those of a channel.
+    fn contests(channel: Option<String>) -> FieldResult<Vec<Contest>> {
+        let channel = channel.as_ref().map(|id| parse_channel(id)).transpose()?;
+        let history = history::HISTORY.locked()?;
+        Ok(history
+            .contests
+            .iter()
+            .enumerate()
+            .map(|(index, record)| (index + 1, record))
+            .filter(|(_, record)| channel.map_or(true, |channel| record.channel == channel))
+            .map(|(id, record)| Contest::new(id, record))
+            .collect())
+    }
+}
+
+pub struct Mutation;
+
+#[juniper::object(Context = Context)]
+impl Mutation {
+    /// Poses a question of `language` in the channel, as `~<command>` would.
+    fn start_quiz(context: &Context, channel: String, language: String) -> FieldResult<Channel> {
+        require_admin(context)?;
+        let id = parse_channel(&channel)?;
+        let lang = LANGUAGES.find(&language).ok_or_else(|| {
+            FieldError::new(
+                format!("unexpected language `{}`", language),
+                juniper::Value::null(),
+            )
+        })?;
+        let (enabled, languages) = {
+            let settings = settings::SETTINGS.locked()?;
+            (settings.is_enabled(id, None), settings.languages(id, None))
+        };
+        if !enabled {
+            return Err(FieldError::new(
+                "channel is not enabled",
+                juniper::Value::null(),
+            ));
+        }
+        if let Some(languages) = languages {
+            if !languages.iter().any(|code| code == lang.as_code()) {
+                return Err(FieldError::new(
+                    format!("`{}` is not allowed in the channel", language),
+                    juniper::Value::null(),
+                ));
+            }
+        }
+        let session = bot::session(id)?;
+        let mut guard = session.locked()?;
+        if !guard.quiz.is_standing_by() {
+            return Err(FieldError::new(
+                "a question is already posed",
+                juniper::Value::null(),
+            ));
+        }
+        let events = guard.pose(lang, &Filter::default(), &mut rand::thread_rng())?;
+        executors::post(&context.chat, id, &events)?;
+        let channel = channel_of(id, &guard);
+        drop(guard);
+        drop(session);
+        bot::release(id)?;
+        Ok(channel)
+    }
+
+    /// Closes the running contest of the channel without announcing its standings.
+    fn abort_contest(context: &Context, channel: String) -> FieldResult<Channel> {
+        require_admin(context)?;
+        let id = parse_channel(&channel)?;
+        let no_contest = || FieldError::new("no contest is running", juniper::Value::null());
+        let session = bot::find_session(id)?.ok_or_else(no_contest)?;
+        let mut guard = session.locked()?;
+        if !guard.quiz.is_contesting() {
+            return Err(no_contest());
+        }
+        let events = guard.abort();
+        executors::post(&context.chat, id, &events)?;
+        let channel = channel_of(id, &guard);
+        drop(guard);
+        drop(session);
+        bot::release(id)?;
+        Ok(channel)
+    }
+}
@@ -0,0 +1,198 @@
+use std::io::{self, BufRead, Read, Write};
+
+/// Largest request body accepted, in bytes.
+pub const MAX_BODY: usize = 1024 * 1024;
+/// Longest request line or header accepted, in bytes.
+pub const MAX_LINE: usize = 8 * 1024;
+/// Most headers accepted in a request.
+pub const MAX_HEADERS: usize = 64;
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Reads a HTTP/1.x request; only `Content-Length` bodies are supported.
+    pub fn read<R: BufRead>(reader: &mut R) -> io::Result<Request> {
+        let mut line = String::new();
+        read_line(reader, &mut line)?;
+        let mut request_line = line.split_whitespace();
+        let method = request_line
+            .next()
+            .ok_or_else(|| invalid("empty request"))?
+            .to_string();
+        let target = request_line
+            .next()
+            .ok_or_else(|| invalid("no request target"))?;
+        let path = target.split('?').next().unwrap_or(target).to_string();
+
+        let mut headers = Vec::new();
+        loop {
+            if read_line(reader, &mut line)? == 0 {
+                return Err(invalid("unexpected end of headers"));
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(invalid("too many headers"));
+            }
+            let colon = header
+                .find(':')
+                .ok_or_else(|| invalid("malformed header"))?;
+            headers.push((
+                header[..colon].trim().to_lowercase(),
+                header[colon + 1..].trim().to_string(),
+            ));
+        }
+
+        let mut request = Request {
+            method,
+            path,
+            headers,
+            body: Vec::new(),
+        };
+        let length = match request.header("content-length") {
+            Some(length) => length
+                .parse::<usize>()
+                .map_err(|_| invalid("invalid content length"))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(invalid("request body is too large"));
+        }
+        request.body.resize(length, 0);
+        reader.read_exact(&mut request.body)?;
+        Ok(request)
+    }
+
+    /// Value of the header; `name` must be lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Token of an `Authorization: Bearer` header.
+    pub fn bearer(&self) -> Option<&str> {
+        self.header("authorization")
+            .filter(|value| value.starts_with("Bearer "))
+            .map(|value| value["Bearer ".len()..].trim())
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+/// Replaces `line` with the next line of at most `MAX_LINE` bytes.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> io::Result<usize> {
+    line.clear();
+    let read = reader.by_ref().take(MAX_LINE as u64).read_line(line)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(invalid("line is too long"));
+    }
+    Ok(read)
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json<S: Into<String>>(status: u16, body: S) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: body.into(),
+        }
+    }
+
+    pub fn text<S: Into<String>>(status: u16, body: S) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    pub fn html<S: Into<String>>(body: S) -> Response {
+        Response {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        writer.write_all(self.body.as_bytes())?;
+        writer.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(request: &str) -> io::Result<Request> {
+        Request::read(&mut request.as_bytes())
+    }
+
+    #[test]
+    fn reads_a_request() {
+        let request = read(
+            "POST /graphql?x=1 HTTP/1.1\r\nContent-Length: 2\r\nAuthorization: Bearer t\r\n\r\n{}",
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/graphql");
+        assert_eq!(request.bearer(), Some("t"));
+        assert_eq!(request.body, b"{}");
+    }
+
+    #[test]
+    fn rejects_long_lines() {
+        let path = "a".repeat(MAX_LINE);
+        assert!(read(&format!("GET /{} HTTP/1.1\r\n\r\n", path)).is_err());
+        let value = "a".repeat(MAX_LINE);
+        assert!(read(&format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", value)).is_err());
+    }
+
+    #[test]
+    fn rejects_too_many_headers() {
+        let headers = "X: y\r\n".repeat(MAX_HEADERS);
+        assert!(read(&format!("GET / HTTP/1.1\r\n{}\r\n", headers)).is_ok());
+        let headers = "X: y\r\n".repeat(MAX_HEADERS + 1);
+        assert!(read(&format!("GET / HTTP/1.1\r\n{}\r\n", headers)).is_err());
+    }
+}
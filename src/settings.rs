@@ -1,39 +1,439 @@
 pub mod permission;
-pub mod prefix;
+pub mod scope;
+pub mod timeout;
 
+use super::error::{BotError, Lock};
+use indexmap::IndexMap;
+use scope::{Options, Source};
+use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use toml;
+use toml::value::{Table, Value};
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+/// Used unless `SETTINGS_DIR` (or `--settings-dir`) is given.
+pub const DEFAULT_DIR: &str = "/tmp/settings";
+
+/// Layout version written by this build; older files are migrated on load.
+pub(crate) const VERSION: u32 = 2;
+
+pub const DEFAULT_PREFIX: &str = "~";
+
+#[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Config {
-    pub(crate) channel: permission::Channel,
-    pub(crate) prefix: prefix::Prefix,
+    pub(crate) version: u32,
+    /// Defaults of the channels in a guild, keyed by guild id.
+    #[serde(default)]
+    pub(crate) guilds: IndexMap<String, Options>,
+    /// Keyed by channel id.
+    #[serde(default)]
+    pub(crate) channels: IndexMap<String, Options>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: VERSION,
+            guilds: IndexMap::new(),
+            channels: IndexMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The guild given by the message, or the one recorded for the channel.
+    fn guild_of(&self, channel: u64, guild: Option<u64>) -> Option<u64> {
+        guild.or_else(|| {
+            self.channels
+                .get(&channel.to_string())
+                .and_then(|options| options.guild)
+        })
+    }
+
+    /// Looks `field` up in the channel, then in its guild.
+    pub(crate) fn lookup<T, F>(
+        &self,
+        channel: u64,
+        guild: Option<u64>,
+        field: F,
+    ) -> (Option<T>, Source)
+    where
+        F: Fn(&Options) -> Option<T>,
+    {
+        let in_channel = self.channels.get(&channel.to_string()).and_then(&field);
+        if in_channel.is_some() {
+            return (in_channel, Source::Channel);
+        }
+        let in_guild = self
+            .guild_of(channel, guild)
+            .and_then(|guild| self.guilds.get(&guild.to_string()))
+            .and_then(&field);
+        if in_guild.is_some() {
+            return (in_guild, Source::Guild);
+        }
+        (None, Source::Default)
+    }
+
+    pub(crate) fn is_enabled(&self, channel: u64, guild: Option<u64>) -> bool {
+        self.lookup(channel, guild, |options| options.enabled)
+            .0
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn prefix(&self, channel: u64, guild: Option<u64>) -> String {
+        self.lookup(channel, guild, |options| options.prefix.clone())
+            .0
+            .unwrap_or_else(|| DEFAULT_PREFIX.to_string())
+    }
+
+    /// Codes of the languages allowed in the channel; `None` allows every language.
+    pub(crate) fn languages(&self, channel: u64, guild: Option<u64>) -> Option<Vec<String>> {
+        self.lookup(channel, guild, |options| options.languages.clone())
+            .0
+    }
+
+    pub(crate) fn timeout(&self, channel: u64, guild: Option<u64>) -> Option<timeout::Limit> {
+        self.lookup(channel, guild, |options| options.timeout)
+            .0
+            .filter(|limit| !limit.is_off())
+    }
+
+    pub(crate) fn x_system(&self, channel: u64, guild: Option<u64>) -> bool {
+        self.lookup(channel, guild, |options| options.x_system)
+            .0
+            .unwrap_or(false)
+    }
+
+    /// Settings of the channel, recording its guild if given.
+    pub(crate) fn channel_mut(&mut self, channel: u64, guild: Option<u64>) -> &mut Options {
+        let options = self
+            .channels
+            .entry(channel.to_string())
+            .or_insert_with(Options::default);
+        if guild.is_some() {
+            options.guild = guild;
+        }
+        options
+    }
+
+    pub(crate) fn guild_mut(&mut self, guild: u64) -> &mut Options {
+        self.guilds
+            .entry(guild.to_string())
+            .or_insert_with(Options::default)
+    }
+
+    /// Channels whose settings are recorded, with their guilds.
+    pub(crate) fn channels(&self) -> impl Iterator<Item = (u64, Option<u64>)> + '_ {
+        self.channels
+            .iter()
+            .filter_map(|(id, options)| Some((id.parse().ok()?, options.guild)))
+    }
 }
 
 lazy_static! {
+    /// Directory of the settings, the contest history and the ratings.
+    pub static ref DIR: PathBuf = env::var_os("SETTINGS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DIR));
     pub(crate) static ref SETTINGS: Arc<Mutex<Config>> = Arc::new(Mutex::new(
-        init_config("/tmp/settings/settings.toml").unwrap()
+        init_config(path("settings.toml")).unwrap_or_else(|err| match err {
+            BotError::SettingsTooNew(_) => panic!("{}; refusing to start", err),
+            err => {
+                println!("could not load settings, using defaults: {}", err);
+                Config::default()
+            }
+        })
     ));
 }
 
-pub(crate) fn init_config<ConfigPath: AsRef<Path>>(path: ConfigPath) -> std::io::Result<Config> {
-    File::open(&path).map_or_else(
-        |_| {
-            let mut f = File::create(&path)?;
-            let buffer = toml::to_string(&Config::default()).unwrap();
-            f.write_all(buffer.as_bytes())?;
-            f.sync_all()?;
-            Ok(Config::default())
-        },
-        |mut file| {
-            let mut buffer = String::new();
-            file.read_to_string(&mut buffer)?;
-            let conf: Config = toml::from_slice(buffer.as_bytes()).unwrap_or_default();
-            Ok(conf)
-        },
-    )
+/// Loads the settings now instead of on first use, so that the bot refuses to start rather
+/// than overwrite settings written by a newer build.
+pub fn init() {
+    lazy_static::initialize(&SETTINGS);
+}
+
+/// Path of `file` in the settings directory.
+pub(crate) fn path(file: &str) -> PathBuf {
+    DIR.join(file)
+}
+
+/// Copies `path` next to itself with the current time appended, returning the copy.
+pub(crate) fn backup(path: &Path) -> io::Result<PathBuf> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(chrono::Utc::now().format(".%Y%m%d%H%M%S.bak").to_string());
+    let backup = path.with_file_name(name);
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+/// Loads the settings, creating the file (and its directory) with the defaults if missing.
+///
+/// Files of an older layout are migrated and written back; files that cannot be read as
+/// any known layout are backed up and replaced with the defaults. Files of a newer layout
+/// are left alone and reported as [`BotError::SettingsTooNew`].
+pub(crate) fn init_config<ConfigPath: AsRef<Path>>(path: ConfigPath) -> Result<Config, BotError> {
+    let path = path.as_ref();
+    let io = |source| BotError::SettingsIo {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io)?;
+    }
+    let buffer = match fs::read_to_string(path) {
+        Ok(buffer) => buffer,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            let conf = Config::default();
+            write_config(path, &conf)?;
+            return Ok(conf);
+        }
+        Err(err) => return Err(io(err)),
+    };
+    let (conf, from) = match parse(&buffer) {
+        Ok(parsed) => parsed,
+        Err(err @ BotError::SettingsTooNew(_)) => return Err(err),
+        Err(err) => {
+            let backup = backup(path).map_err(io)?;
+            println!(
+                "{}: {}; kept as {}, using defaults",
+                path.display(),
+                err,
+                backup.display()
+            );
+            let conf = Config::default();
+            write_config(path, &conf)?;
+            return Ok(conf);
+        }
+    };
+    if from != VERSION {
+        let backup = backup(path).map_err(io)?;
+        println!(
+            "{}: migrated from version {} to {}; the old file is kept as {}",
+            path.display(),
+            from,
+            VERSION,
+            backup.display()
+        );
+        write_config(path, &conf)?;
+    }
+    Ok(conf)
+}
+
+/// Reads any known layout, returning the settings and the version they were written in.
+fn parse(buffer: &str) -> Result<(Config, u32), BotError> {
+    let mut table: Table = toml::from_str(buffer).map_err(BotError::SettingsParse)?;
+    let from = match table.get("version") {
+        None => 0,
+        Some(Value::Integer(version)) if (0..=VERSION as i64).contains(version) => *version as u32,
+        Some(Value::Integer(version)) if *version > VERSION as i64 => {
+            return Err(BotError::SettingsTooNew(*version))
+        }
+        Some(version) => return Err(BotError::SettingsVersion(version.to_string())),
+    };
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(&mut table);
+    }
+    table.insert("version".to_string(), Value::Integer(VERSION as i64));
+    let conf = Value::Table(table)
+        .try_into::<Config>()
+        .map_err(BotError::SettingsParse)?;
+    Ok((conf, from))
+}
+
+/// `MIGRATIONS[n]` turns a layout of version `n` into version `n + 1`.
+const MIGRATIONS: [fn(&mut Table); VERSION as usize] = [v0_to_v1, v1_to_v2];
+
+/// Files written before `version` was introduced may lack whole sections.
+fn v0_to_v1(table: &mut Table) {
+    let sections = [
+        ("channel", "enabled", Value::Array(Vec::new())),
+        ("prefix", "dynamic", Value::Table(Table::new())),
+        ("timeout", "channels", Value::Table(Table::new())),
+    ];
+    for (section, field, empty) in sections.iter() {
+        if let Value::Table(section) = table
+            .entry(section.to_string())
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            section
+                .entry(field.to_string())
+                .or_insert_with(|| empty.clone());
+        }
+    }
+}
+
+/// Channel settings were split by kind into `channel.enabled`, `prefix.dynamic` and
+/// `timeout.channels`; they are gathered under `channels.<id>`.
+fn v1_to_v2(table: &mut Table) {
+    let mut channels = Table::new();
+    let mut set = |channel: String, key: &str, value: Value| {
+        if let Value::Table(options) = channels
+            .entry(channel)
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            options.insert(key.to_string(), value);
+        }
+    };
+    if let Some(Value::Table(mut channel)) = table.remove("channel") {
+        if let Some(Value::Array(enabled)) = channel.remove("enabled") {
+            for id in enabled {
+                if let Value::Integer(id) = id {
+                    set(id.to_string(), "enabled", Value::Boolean(true));
+                }
+            }
+        }
+    }
+    if let Some(Value::Table(mut prefix)) = table.remove("prefix") {
+        if let Some(Value::Table(dynamic)) = prefix.remove("dynamic") {
+            for (id, prefix) in dynamic {
+                set(id, "prefix", prefix);
+            }
+        }
+    }
+    if let Some(Value::Table(mut timeout)) = table.remove("timeout") {
+        if let Some(Value::Table(limits)) = timeout.remove("channels") {
+            for (id, limit) in limits {
+                set(id, "timeout", limit);
+            }
+        }
+    }
+    table.insert("guilds".to_string(), Value::Table(Table::new()));
+    table.insert("channels".to_string(), Value::Table(channels));
+}
+
+/// Records the guild of a channel the first time it is seen, so that guild defaults also
+/// apply where no message is at hand (time limits, the HTTP API).
+pub(crate) fn record_guild(channel: u64, guild: u64) -> Result<(), BotError> {
+    {
+        let mut settings = SETTINGS.locked()?;
+        if settings.guild_of(channel, None) == Some(guild) {
+            return Ok(());
+        }
+        settings.channel_mut(channel, Some(guild));
+    }
+    sync_setting()
+}
+
+fn write_config(path: &Path, conf: &Config) -> Result<(), BotError> {
+    let io = |source| BotError::SettingsIo {
+        path: path.to_path_buf(),
+        source,
+    };
+    write_atomic(path, toml::to_string(conf)?.as_bytes()).map_err(io)
+}
+
+/// A TOML file of the settings directory kept in memory, such as the history or the ratings.
+#[derive(Debug)]
+pub(crate) struct Stored<T> {
+    path: PathBuf,
+    value: T,
+    /// The file could not be read, so it is never overwritten with what is in memory.
+    unreadable: bool,
+}
+
+impl<T: Default + DeserializeOwned + serde::Serialize> Stored<T> {
+    /// Loads the file; a missing one gives the default and an unparsable one is backed up
+    /// first. Any other error is reported and the default is kept without ever being saved.
+    pub(crate) fn load(path: PathBuf) -> Stored<T> {
+        let (value, unreadable) = match read(&path) {
+            Ok(value) => (value, false),
+            Err(err) => {
+                println!(
+                    "could not load {}, it will not be saved: {}",
+                    path.display(),
+                    err
+                );
+                (T::default(), true)
+            }
+        };
+        Stored {
+            path,
+            value,
+            unreadable,
+        }
+    }
+
+    /// Writes the value back to the file.
+    pub(crate) fn save(&self) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+
+        let path = self.path.display();
+        if self.unreadable {
+            anyhow::bail!("{} could not be loaded, so it is not overwritten", path);
+        }
+        let contents = toml::to_string(&self.value).with_context(|| path.to_string())?;
+        write_atomic(&self.path, contents.as_bytes()).with_context(|| path.to_string())
+    }
+}
+
+fn read<T: Default + DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let buffer = match fs::read_to_string(path) {
+        Ok(buffer) => buffer,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(err) => return Err(err),
+    };
+    toml::from_str(&buffer).or_else(|err| {
+        let backup = backup(path)?;
+        println!(
+            "{}: {}; kept as {}, starting empty",
+            path.display(),
+            err,
+            backup.display()
+        );
+        Ok(T::default())
+    })
+}
+
+impl<T> Deref for Stored<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Stored<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Replaces the file at `path` through a temporary file in the same directory, so that a crash
+/// never leaves it half written.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+/// Writes the current settings back to disk.
+pub(crate) fn sync_setting() -> Result<(), BotError> {
+    write_config(&path("settings.toml"), &*SETTINGS.locked()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let newer = format!("version = {}\n", VERSION + 1);
+        match parse(&newer) {
+            Err(BotError::SettingsTooNew(version)) => assert_eq!(version, VERSION as i64 + 1),
+            other => panic!("{:?}", other.map(|(_, from)| from)),
+        }
+        assert!(parse(&format!("version = {}\n", VERSION)).is_ok());
+    }
 }
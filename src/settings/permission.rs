@@ -1,5 +1,41 @@
-use serde_derive::{Deserialize, Serialize};
-#[derive(Default, Debug, Serialize, Deserialize)]
-pub(crate) struct Channel {
-    pub(crate) enabled: Vec<u64>,
+use super::super::chat::Message;
+use super::Config;
+use std::collections::HashSet;
+
+/// Who may run a command; each level includes the ones above it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Everyone,
+    /// Members with one of the moderator roles of the channel.
+    Moderator,
+    /// Members with the administrator permission of the guild.
+    Administrator,
+    /// Owners of the bot application.
+    Owner,
+}
+
+impl Config {
+    /// Roles treated as moderators in the channel.
+    pub(crate) fn moderators(&self, channel: u64, guild: Option<u64>) -> Vec<u64> {
+        self.lookup(channel, guild, |options| options.moderators.clone())
+            .0
+            .unwrap_or_default()
+    }
+}
+
+/// Level of the author of `msg`; everybody administrates their direct messages.
+pub(crate) fn level(config: &Config, owners: &HashSet<u64>, msg: &Message) -> Level {
+    if owners.contains(&msg.author_id) {
+        Level::Owner
+    } else if msg.administrator || msg.guild.is_none() {
+        Level::Administrator
+    } else if config
+        .moderators(msg.channel, msg.guild)
+        .iter()
+        .any(|role| msg.roles.contains(role))
+    {
+        Level::Moderator
+    } else {
+        Level::Everyone
+    }
 }
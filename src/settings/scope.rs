@@ -0,0 +1,28 @@
+use super::timeout::Limit;
+use serde_derive::{Deserialize, Serialize};
+
+/// Where an effective setting comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Channel,
+    Guild,
+    Default,
+}
+
+/// Settings of a guild or a channel; those left unset are inherited from the guild, then
+/// from the defaults.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Options {
+    /// Guild of a channel, recorded from its messages; unused for guilds.
+    pub(crate) guild: Option<u64>,
+    pub(crate) enabled: Option<bool>,
+    pub(crate) prefix: Option<String>,
+    /// Codes of the languages that may be quizzed; every language when unset.
+    pub(crate) languages: Option<Vec<String>>,
+    /// Role ids whose members may run the moderator commands.
+    pub(crate) moderators: Option<Vec<u64>>,
+    /// `seconds = 0` lifts a limit inherited from the guild.
+    pub(crate) timeout: Option<Limit>,
+    /// Shows Esperanto letters in the x-system, e.g. ĉ as cx.
+    pub(crate) x_system: Option<bool>,
+}
@@ -0,0 +1,20 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Time limit of a question; `warning` is the remaining time to remind at.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Limit {
+    pub(crate) seconds: u64,
+    pub(crate) warning: Option<u64>,
+}
+
+impl Limit {
+    /// Overrides an inherited limit with none.
+    pub(crate) const OFF: Limit = Limit {
+        seconds: 0,
+        warning: None,
+    };
+
+    pub(crate) fn is_off(&self) -> bool {
+        self.seconds == 0
+    }
+}
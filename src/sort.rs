@@ -1,13 +1,23 @@
 use itertools::Itertools;
+use unicode_normalization::UnicodeNormalization;
 use unicode_segmentation::UnicodeSegmentation;
 
+/// Sorts the extended grapheme clusters of the NFC form, so that composed and decomposed
+/// spellings of a word sort alike.
 pub trait Sorted {
     fn sorted(&self) -> String;
 }
 
 impl Sorted for String {
     fn sorted(&self) -> Self {
-        UnicodeSegmentation::graphemes(self.as_str(), true)
+        self.as_str().sorted()
+    }
+}
+
+impl Sorted for str {
+    fn sorted(&self) -> String {
+        let composed = self.nfc().collect::<String>();
+        UnicodeSegmentation::graphemes(composed.as_str(), true)
             .sorted()
             .collect::<String>()
     }
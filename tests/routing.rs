@@ -0,0 +1,263 @@
+use mitama_test_bot::bot;
+use mitama_test_bot::harness::{Harness, ADMIN, OWNER};
+use mitama_test_bot::language::{to_x_system, Lang};
+use unicode_normalization::UnicodeNormalization;
+
+// Channels are process-wide state, so every test plays in its own channel.
+
+#[test]
+fn commands_are_ignored_until_enabled() {
+    let mut bot = Harness::new();
+    assert!(bot.send("alice", 1001, "~en").is_empty());
+    assert_eq!(
+        bot.send(ADMIN, 1001, "~enable"),
+        vec!["このチャンネルでソートなぞなぞが有効になりました。"]
+    );
+    let posted = bot.send("alice", 1001, "~en");
+    assert!(posted[0].starts_with("ソートなぞなぞ ソート前の"));
+    bot.send(ADMIN, 1001, "~disable");
+}
+
+#[test]
+fn contest_standings() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1002, "~enable");
+    let posted = bot.send("alice", 1002, "~contest 3 en");
+    assert_eq!(posted.len(), 1);
+    assert!(posted[0].starts_with("3問のコンテストを始めます。"));
+
+    for (player, answer) in &[("alice", "apple"), ("bob", "water")] {
+        bot.pose(1002, answer);
+        let posted = bot.send(player, 1002, answer);
+        assert!(posted[0].starts_with(&format!("{} さん、正解です！", player)));
+        assert!(posted[0].contains("問 "));
+    }
+    let posted = bot.send("carol", 1002, "~giveup");
+    let last = posted.concat();
+    assert!(last.contains("3問連続のコンテストが終了しました。"));
+    assert!(last.contains(": alice, 1 AC"));
+    assert!(last.contains(": bob, 1 AC"));
+    assert!(bot.answer(1002).is_none());
+}
+
+#[test]
+fn busy_channel_rejects_new_quiz() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1003, "~enable");
+    bot.send("alice", 1003, "~en");
+    let posted = bot.send("bob", 1003, "~contest 3 en");
+    assert!(posted[0].starts_with("前回の出題が解かれていません"));
+    bot.send("bob", 1003, "~giveup");
+}
+
+#[test]
+fn prefix_of_the_channel() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1004, "~enable");
+    bot.send(ADMIN, 1004, "~prefix set !");
+    assert!(bot.send("alice", 1004, "~languages").is_empty());
+    assert!(!bot.send("alice", 1004, "!languages").is_empty());
+    bot.send(ADMIN, 1004, "!prefix set ~");
+}
+
+#[test]
+fn rate_limit_per_user() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1005, "~enable");
+    let first = bot.message("alice", 1005, "~languages");
+    let mut second = first.clone();
+    second.timestamp = first.timestamp + chrono::Duration::milliseconds(500);
+    assert!(!bot.deliver(&first).is_empty());
+    assert!(bot.deliver(&second).is_empty());
+    assert!(!bot.send("bob", 1005, "~languages").is_empty());
+
+    let first = bot.message("carol", 1005, "~en");
+    let mut second = first.clone();
+    second.timestamp = first.timestamp + chrono::Duration::milliseconds(500);
+    assert!(!bot.deliver(&first).is_empty());
+    assert!(bot.deliver(&second).is_empty());
+    bot.send("carol", 1005, "~giveup");
+}
+
+#[test]
+fn owners_only_commands() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1006, "~enable");
+    assert_eq!(
+        bot.send("alice", 1006, "~reload en"),
+        vec!["`~reload` を実行できるのはbotのオーナーのみです。"]
+    );
+    let posted = bot.send(OWNER, 1006, "~reload en");
+    assert!(posted[0].starts_with("en: reloaded"));
+}
+
+#[test]
+fn guild_defaults_and_channel_overrides() {
+    let mut bot = Harness::new();
+    // guild 2 is used by this test alone.
+    let mut send = |channel, content: &str| {
+        let mut msg = bot.message(ADMIN, channel, content);
+        msg.guild = Some(2);
+        bot.deliver(&msg)
+    };
+    let posted = send(1007, "~config set enabled on --guild");
+    assert!(posted[0].starts_with("このサーバーの設定を変更しました。"));
+    assert!(posted[0].contains("enabled: `on` (サーバー)"));
+    send(1007, "~config set languages en --guild");
+    assert_eq!(
+        send(1008, "~ja"),
+        vec!["このチャンネルで出題できる言語は en です。"]
+    );
+
+    send(1008, "~prefix set !");
+    let posted = send(1008, "!config");
+    assert!(posted[0].contains("prefix: `!` (チャンネル)"));
+    assert!(posted[0].contains("languages: `en` (サーバー)"));
+    assert!(posted[0].contains("timeout: なし (既定)"));
+    send(1008, "!config unset prefix");
+
+    send(1009, "~disable");
+    assert!(send(1009, "~en").is_empty());
+    send(1009, "~config unset enabled");
+    assert!(send(1009, "~en")[0].starts_with("ソートなぞなぞ ソート前の"));
+    send(1009, "~giveup");
+}
+
+#[test]
+fn moderator_roles() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1010, "~enable");
+    bot.send(ADMIN, 1010, "~config set moderators <@&77>");
+    assert_eq!(
+        bot.send("alice", 1010, "~unrated"),
+        vec!["`~unrated` を実行できるのはモデレーターのロールを持つメンバーか管理者のみです。"]
+    );
+
+    let mut msg = bot.message("alice", 1010, "~prefix");
+    msg.roles.push(77);
+    assert_eq!(
+        bot.deliver(&msg),
+        vec!["このチャンネルのprefixは現在 `~` です。"]
+    );
+    let mut msg = bot.message("alice", 1010, "~config set moderators 78");
+    msg.roles.push(77);
+    assert_eq!(
+        bot.deliver(&msg),
+        vec!["サーバーの設定とモデレーターのロールは管理者のみ変更できます。"]
+    );
+    let posted = bot.send(OWNER, 1010, "~config set moderators <@&78>");
+    assert!(posted[0].contains("moderators"), "{:?}", posted);
+}
+
+#[test]
+fn japanese_answers_in_either_kana() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1011, "~enable");
+    bot.send("alice", 1011, "~ja");
+    bot.pose(1011, "アイデアしょうひん");
+    let posted = bot.send("alice", 1011, "あいでアショウヒン");
+    assert!(posted[0].starts_with("alice さん、正解です！\n正解は\"アイデアしょうひん\"でした！"));
+}
+
+#[test]
+fn japanese_answers_in_romaji() {
+    let _bot = Harness::new();
+    let ja = "ja".parse::<Lang>().unwrap();
+    for romaji in &["aidiashouhin", "aideasyouhin", "aideashōhin"] {
+        assert_eq!(ja.read(romaji), ja.read("アイデアしょうひん"), "{}", romaji);
+    }
+    let dictionary = ja.dictionary().unwrap();
+    for word in &["おうばんぶるまい", "おおばんぶるまい"] {
+        assert_eq!(dictionary.spelling(word).map(String::as_str), Some(*word));
+    }
+}
+
+#[test]
+fn french_answers_without_accents() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1012, "~enable");
+    bot.send("alice", 1012, "~fr");
+    bot.pose(1012, "élève");
+    let posted = bot.send("alice", 1012, "eleve");
+    assert!(posted[0].starts_with("alice さん、正解です！\n正解は\"élève\"でした！"));
+}
+
+#[test]
+fn esperanto_in_x_system() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1013, "~enable");
+    let posted = bot.send(ADMIN, 1013, "~config set x_system on");
+    assert!(posted[0].contains("x_system: `on` (チャンネル)"));
+    let posted = bot.send("alice", 1013, "~eo");
+    assert!(!posted[0].contains(|c| "ĉĝĥĵŝŭ".contains(c)));
+    for (answer, guess) in &[
+        ("ĉambro", "cxambro"),
+        ("ŝipo", "shipo"),
+        ("ĉielo", "chielo"),
+    ] {
+        bot.pose(1013, answer);
+        let posted = bot.send("alice", 1013, guess);
+        assert!(posted[0].starts_with(&format!(
+            "alice さん、正解です！\n正解は\"{}\"でした！",
+            to_x_system(answer)
+        )));
+        bot.send("alice", 1013, "~eo");
+    }
+    bot.send("alice", 1013, "~giveup");
+}
+
+#[test]
+fn russian_answers_in_latin() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1014, "~enable");
+    bot.send("alice", 1014, "~ru");
+    for (answer, latin) in &[("женщина", "zhenshhina"), ("жизнь", "zhizn`")] {
+        bot.pose(1014, answer);
+        let posted = bot.send("alice", 1014, latin);
+        assert!(posted[0].starts_with(&format!(
+            "alice さん、正解です！\n正解は\"{}\"でした！",
+            answer
+        )));
+        bot.send("alice", 1014, "~ru");
+    }
+    bot.send("alice", 1014, "~giveup");
+}
+
+#[test]
+fn decomposed_answers() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1015, "~enable");
+    bot.send("alice", 1015, "~de");
+    bot.pose(1015, "abhängen");
+    let posted = bot.send("alice", 1015, &"abhängen".nfd().collect::<String>());
+    assert!(posted[0].starts_with("alice さん、正解です！\n正解は\"abhängen\"でした！"));
+}
+
+#[test]
+fn idle_channels_keep_no_session() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1016, "~enable");
+    bot.send("alice", 1016, "hello");
+    assert!(bot::find_session(1016).unwrap().is_none());
+    bot.send("alice", 1016, "~en");
+    assert!(bot::find_session(1016).unwrap().is_some());
+    bot.send("alice", 1016, "~giveup");
+    assert!(bot::find_session(1016).unwrap().is_none());
+}
+
+#[test]
+fn questions_time_out_once() {
+    let mut bot = Harness::new();
+    bot.send(ADMIN, 1017, "~enable");
+    bot.send(ADMIN, 1017, "~timeout set 1");
+    for _ in 0..3 {
+        bot.send("alice", 1017, "~en");
+        bot.send("alice", 1017, "~giveup");
+    }
+    bot.send("alice", 1017, "~en");
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    let posted = bot.chat.take();
+    assert_eq!(posted.len(), 1);
+    assert!(posted[0].1.starts_with("時間切れです！"));
+    assert!(bot::find_session(1017).unwrap().is_none());
+}
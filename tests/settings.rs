@@ -0,0 +1,31 @@
+use mitama_test_bot::harness::Harness;
+use std::{env, fs};
+
+// The settings directory is read once per process, so this file holds a single test.
+
+#[test]
+fn unversioned_settings_are_migrated() {
+    let dir = env::temp_dir().join(format!("nazonazo-settings-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("settings.toml"),
+        "[channel]\nenabled = [2001]\n\n[prefix.dynamic]\n2001 = \"!\"\n",
+    )
+    .unwrap();
+    env::set_var("SETTINGS_DIR", &dir);
+
+    let mut bot = Harness::new();
+    let posted = bot.send("alice", 2001, "!en");
+    assert!(posted[0].starts_with("ソートなぞなぞ ソート前の"));
+
+    let migrated = fs::read_to_string(dir.join("settings.toml")).unwrap();
+    assert!(migrated.starts_with("version = 2\n"));
+    assert!(migrated.contains("[channels.2001]"));
+    let backups = fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".bak"))
+        .count();
+    assert_eq!(backups, 1);
+    fs::remove_dir_all(&dir).unwrap();
+}